@@ -0,0 +1,48 @@
+use clap::Parser;
+
+use super::verify_file;
+
+/// 和 age v1 格式兼容，可以和 age / rage 互相加解密
+/// 参数和 age 保持一致：输入为位置参数，-i 为 identity 文件
+#[derive(Debug, Parser)]
+pub enum AgeSubCommand {
+    #[command(
+        name = "encrypt",
+        about = "Encrypt to age X25519 recipients or with a passphrase"
+    )]
+    Encrypt(AgeEncryptOpts),
+
+    #[command(name = "decrypt", about = "Decrypt an age file with identity files")]
+    Decrypt(AgeDecryptOpts),
+}
+
+#[derive(Debug, Parser)]
+pub struct AgeEncryptOpts {
+    #[arg(value_parser = verify_file, default_value = "-")]
+    pub input: String,
+
+    // - 表示 stdout
+    #[arg(short, long, default_value = "-")]
+    pub output: String,
+
+    // age1... 格式的公钥，可以指定多个
+    #[arg(short, long = "recipient", required_unless_present = "passphrase")]
+    pub recipients: Vec<String>,
+
+    // 使用 scrypt 口令加密，只能单独使用
+    #[arg(short, long, conflicts_with = "recipients")]
+    pub passphrase: bool,
+}
+
+#[derive(Debug, Parser)]
+pub struct AgeDecryptOpts {
+    #[arg(value_parser = verify_file, default_value = "-")]
+    pub input: String,
+
+    #[arg(short, long, default_value = "-")]
+    pub output: String,
+
+    // AGE-SECRET-KEY-1... 格式的 identity 文件，口令加密的文件不需要
+    #[arg(short, long = "identity", value_parser = verify_file)]
+    pub identities: Vec<String>,
+}
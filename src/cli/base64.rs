@@ -1,7 +1,7 @@
 use std::fmt::{Display, Formatter};
 use std::str::FromStr;
 
-use clap::Parser;
+use clap::{Args, Parser};
 
 use super::verify_file;
 
@@ -26,14 +26,33 @@ pub enum Base64SubCommand {
     Decode(Base64DecodeOpts),
 }
 
+// --data-uri / --pem 的格式是固定的，这些选项会被忽略，直接报错
+const ARMOR_CONFLICTS: [&str; 5] = ["format", "pad", "no_pad", "wrap", "multibase"];
+
 #[derive(Debug, Parser)]
 pub struct Base64EncodeOpts {
     // 不加 pub ，外面 使用时无法通过 . 获取
     #[arg(short, long, value_parser = verify_file, default_value = "-")]
     pub input: String,
 
+    // - 表示 stdout
+    #[arg(short, long, default_value = "-")]
+    pub output: String,
+
     #[arg(long, value_parser = parse_base64_format, default_value = "standard")]
     pub format: Base64Format,
+
+    // 输出 data:<mime>;base64,...，MIME 类型根据文件头和扩展名判断
+    // 固定使用带 padding 的标准 base64，不换行，不能和其他格式选项一起使用
+    #[arg(long, conflicts_with = "pem", conflicts_with_all = ARMOR_CONFLICTS)]
+    pub data_uri: bool,
+
+    // 输出 -----BEGIN LABEL----- 包裹的 PEM，固定为标准 base64，每行 64 个字符
+    #[arg(long, value_name = "LABEL", conflicts_with_all = ARMOR_CONFLICTS)]
+    pub pem: Option<String>,
+
+    #[command(flatten)]
+    pub style: EncodeStyle,
 }
 
 #[derive(Debug, Parser)]
@@ -41,8 +60,174 @@ pub struct Base64DecodeOpts {
     #[arg(short, long, value_parser = verify_file, default_value = "-")]
     pub input: String,
 
+    // - 表示 stdout
+    #[arg(short, long, default_value = "-")]
+    pub output: String,
+
     #[arg(long, value_parser = parse_base64_format, default_value = "standard")]
     pub format: Base64Format,
+
+    #[command(flatten)]
+    pub style: DecodeStyle,
+}
+
+/// rcli encode / rcli decode，base64 子命令是其中 base64 / base64url 的简写
+#[derive(Debug, Parser)]
+pub struct EncodeOpts {
+    #[arg(short, long, value_parser = verify_file, default_value = "-")]
+    pub input: String,
+
+    // - 表示 stdout
+    #[arg(short, long, default_value = "-")]
+    pub output: String,
+
+    #[arg(long, value_parser = parse_encoding, default_value = "base64")]
+    pub encoding: Encoding,
+
+    #[command(flatten)]
+    pub style: EncodeStyle,
+}
+
+#[derive(Debug, Parser)]
+pub struct DecodeOpts {
+    #[arg(short, long, value_parser = verify_file, default_value = "-")]
+    pub input: String,
+
+    // - 表示 stdout
+    #[arg(short, long, default_value = "-")]
+    pub output: String,
+
+    #[arg(long, value_parser = parse_encoding, default_value = "base64")]
+    pub encoding: Encoding,
+
+    // 自动识别 base64 / base64url / base58 / base32 / hex，有歧义时在 stderr 中提示
+    #[arg(long, conflicts_with = "encoding")]
+    pub auto: bool,
+
+    #[command(flatten)]
+    pub style: DecodeStyle,
+}
+
+/// 编码输出的 padding 和换行，padding 只对 base64 / base32 有效，其他编码指定时报错
+/// 换行对所有编码有效，解码时忽略所有空白字符；base32 解码没有 padding 的数据需要 --no-pad
+#[derive(Debug, Clone, Copy, Default, Args)]
+pub struct EncodeStyle {
+    // 默认 standard 和 base32 带 padding，url safe 不带
+    #[arg(long, conflicts_with = "no_pad")]
+    pub pad: bool,
+
+    #[arg(long)]
+    pub no_pad: bool,
+
+    // 每 N 个字符换行，MIME 使用 76，0 表示不换行，和 base64 -w 一致
+    #[arg(short, long, default_value_t = 0)]
+    pub wrap: usize,
+
+    // 加上 multibase 前缀，例如 base58 为 z，base32 为 b，base64 为 m
+    #[arg(long)]
+    pub multibase: bool,
+}
+
+/// 解码默认是宽松的：忽略空白字符，带不带 padding 都接受
+/// --strict 时只对 base64 生效，要求 padding 符合 --pad / --no-pad（或字母表的默认值），且不能有多余的 bit
+#[derive(Debug, Clone, Copy, Default, Args)]
+pub struct DecodeStyle {
+    #[arg(long, conflicts_with = "no_pad")]
+    pub pad: bool,
+
+    #[arg(long)]
+    pub no_pad: bool,
+
+    #[arg(long)]
+    pub strict: bool,
+
+    // 根据 multibase 前缀确定编码，忽略 --encoding
+    #[arg(long)]
+    pub multibase: bool,
+
+    // 以 hexdump 的形式输出解码结果，便于在终端中查看二进制数据
+    #[arg(long)]
+    pub hexdump: bool,
+}
+
+impl EncodeStyle {
+    pub fn padding(&self) -> Option<bool> {
+        padding(self.pad, self.no_pad)
+    }
+}
+
+impl DecodeStyle {
+    pub fn padding(&self) -> Option<bool> {
+        padding(self.pad, self.no_pad)
+    }
+}
+
+fn padding(pad: bool, no_pad: bool) -> Option<bool> {
+    match (pad, no_pad) {
+        (true, _) => Some(true),
+        (_, true) => Some(false),
+        _ => None,
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Encoding {
+    Base64,
+    // url safe，不带 padding
+    Base64Url,
+    Base58,
+    // base58 + 4 字节 double sha256 校验和
+    Base58Check,
+    Base32,
+    Base32Hex,
+    Hex,
+    Z85,
+    Ascii85,
+}
+
+fn parse_encoding(encoding: &str) -> anyhow::Result<Encoding, anyhow::Error> {
+    encoding.parse()
+}
+
+impl From<Encoding> for &'static str {
+    fn from(encoding: Encoding) -> Self {
+        match encoding {
+            Encoding::Base64 => "base64",
+            Encoding::Base64Url => "base64url",
+            Encoding::Base58 => "base58",
+            Encoding::Base58Check => "base58check",
+            Encoding::Base32 => "base32",
+            Encoding::Base32Hex => "base32hex",
+            Encoding::Hex => "hex",
+            Encoding::Z85 => "z85",
+            Encoding::Ascii85 => "ascii85",
+        }
+    }
+}
+
+impl FromStr for Encoding {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "base64" => Ok(Encoding::Base64),
+            "base64url" => Ok(Encoding::Base64Url),
+            "base58" => Ok(Encoding::Base58),
+            "base58check" => Ok(Encoding::Base58Check),
+            "base32" => Ok(Encoding::Base32),
+            "base32hex" => Ok(Encoding::Base32Hex),
+            "hex" => Ok(Encoding::Hex),
+            "z85" => Ok(Encoding::Z85),
+            "ascii85" => Ok(Encoding::Ascii85),
+            _ => Err(anyhow::anyhow!("Invalid encoding")),
+        }
+    }
+}
+
+impl Display for Encoding {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", Into::<&str>::into(*self))
+    }
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -51,6 +236,15 @@ pub enum Base64Format {
     UrlSafe,
 }
 
+impl From<Base64Format> for Encoding {
+    fn from(format: Base64Format) -> Self {
+        match format {
+            Base64Format::Standard => Encoding::Base64,
+            Base64Format::UrlSafe => Encoding::Base64Url,
+        }
+    }
+}
+
 fn parse_base64_format(format: &str) -> anyhow::Result<Base64Format, anyhow::Error> {
     format.parse()
 }
@@ -1,19 +1,242 @@
-use clap::Parser;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+use clap::{ArgAction, Args, Parser};
+use serde::Serialize;
+
+use super::verify_file;
 
 #[derive(Debug, Parser)]
+#[command(args_conflicts_with_subcommands = true)]
 pub struct GenPassOpts {
+    #[command(subcommand)]
+    pub cmd: Option<GenPassSubCommand>,
+
+    #[command(flatten)]
+    pub policy: PasswordPolicy,
+
+    // 由音节组成，便于电话中口述，但熵比同样长度的随机密码低
+    #[arg(long)]
+    pub pronounceable: bool,
+
+    // 一次生成多个密码，label 的数量多于 count 时，以 label 的数量为准
+    #[arg(short, long, default_value_t = 1)]
+    pub count: usize,
+
+    // --label db_pass,redis_pass
+    #[arg(long, value_delimiter = ',')]
+    pub label: Vec<String>,
+
+    #[arg(long, value_parser = parse_genpass_format, default_value = "plain")]
+    pub format: GenPassFormat,
+
+    // 写入文件时权限为 0600，不指定则输出到 stdout
+    #[arg(short, long)]
+    pub output: Option<String>,
+
+    // 默认使用操作系统的 CSPRNG，chacha 只用于测试，必须配合 --seed 使用
+    #[arg(long, value_parser = parse_rng_source, default_value = "os")]
+    pub rng: RngSource,
+
+    // hex 编码，任意长度，经过 blake3 得到 ChaCha20 的 key
+    // 只对直接生成密码有效，和 derive / pin / recovery-codes 一起使用时 clap 会报错
+    #[arg(long)]
+    pub seed: Option<String>,
+
+    // 在 stderr 中输出随机源、字母表、熵等生成细节
+    #[arg(long)]
+    pub explain: bool,
+}
+
+#[derive(Debug, Parser)]
+pub enum GenPassSubCommand {
+    #[command(
+        name = "derive",
+        about = "Derive a reproducible site-specific password from a master secret"
+    )]
+    Derive(GenPassDeriveOpts),
+
+    #[command(name = "pin", about = "Generate a numeric PIN")]
+    Pin(GenPassPinOpts),
+
+    #[command(
+        name = "recovery-codes",
+        about = "Generate backup/recovery codes in Crockford base32"
+    )]
+    RecoveryCodes(RecoveryCodesOpts),
+}
+
+#[derive(Debug, Parser)]
+pub struct GenPassDeriveOpts {
+    #[arg(long)]
+    pub site: String,
+
+    #[arg(long)]
+    pub user: String,
+
+    // 站点要求改密码时，counter + 1 即可得到新的密码
+    #[arg(long, default_value_t = 1)]
+    pub counter: u32,
+
+    // 不指定则从 TTY 读取 master secret，避免出现在 shell history 中
+    #[arg(long, value_parser = verify_file)]
+    pub master_file: Option<String>,
+
+    #[command(flatten)]
+    pub policy: PasswordPolicy,
+}
+
+#[derive(Debug, Parser)]
+pub struct GenPassPinOpts {
+    #[arg(short, long, default_value_t = 6)]
+    pub length: u8,
+}
+
+#[derive(Debug, Parser)]
+pub struct RecoveryCodesOpts {
+    #[arg(short, long, default_value_t = 10)]
+    pub count: usize,
+
+    // 4x4 表示 4 组，每组 4 个字符，例如 ABCD-EFGH-JKMN-PQRS
+    #[arg(long, value_parser = parse_code_groups, default_value = "4x4")]
+    pub groups: CodeGroups,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct CodeGroups {
+    pub groups: usize,
+    pub size: usize,
+}
+
+fn parse_code_groups(groups: &str) -> anyhow::Result<CodeGroups, anyhow::Error> {
+    groups.parse()
+}
+
+impl FromStr for CodeGroups {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (groups, size) = s
+            .split_once('x')
+            .ok_or_else(|| anyhow::anyhow!("Invalid groups, expected <groups>x<size>"))?;
+        let groups: usize = groups.parse()?;
+        let size: usize = size.parse()?;
+        if groups == 0 || size == 0 {
+            return Err(anyhow::anyhow!("Groups and group size must be positive"));
+        }
+
+        Ok(CodeGroups { groups, size })
+    }
+}
+
+impl Display for CodeGroups {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}x{}", self.groups, self.size)
+    }
+}
+
+/// 密码的组成规则，随机生成和 derive 共用
+/// bool 默认为 true，使用 ArgAction::Set 才能通过 --symbol false 关闭
+#[derive(Debug, Clone, Copy, Args, Serialize)]
+pub struct PasswordPolicy {
     #[arg(short, long, default_value_t = 16)]
     pub length: u8,
 
-    #[arg(long, default_value_t = true)]
+    // 按熵指定强度，根据字母表自动计算需要的长度
+    #[arg(long, conflicts_with = "length")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bits: Option<u32>,
+
+    #[arg(long, default_value_t = true, action = ArgAction::Set)]
     pub uppercase: bool,
 
-    #[arg(long, default_value_t = true)]
+    #[arg(long, default_value_t = true, action = ArgAction::Set)]
     pub lowercase: bool,
 
-    #[arg(long, default_value_t = true)]
+    #[arg(long, default_value_t = true, action = ArgAction::Set)]
     pub number: bool,
 
-    #[arg(long, default_value_t = true)]
+    #[arg(long, default_value_t = true, action = ArgAction::Set)]
     pub symbol: bool,
 }
+
+#[derive(Debug, Clone, Copy)]
+pub enum RngSource {
+    Os,
+    // seeded ChaCha20，结果可复现，仅用于测试
+    ChaCha,
+}
+
+fn parse_rng_source(source: &str) -> anyhow::Result<RngSource, anyhow::Error> {
+    source.parse()
+}
+
+impl From<RngSource> for &'static str {
+    fn from(source: RngSource) -> Self {
+        match source {
+            RngSource::Os => "os",
+            RngSource::ChaCha => "chacha",
+        }
+    }
+}
+
+impl FromStr for RngSource {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "os" => Ok(RngSource::Os),
+            "chacha" => Ok(RngSource::ChaCha),
+            _ => Err(anyhow::anyhow!("Invalid rng source")),
+        }
+    }
+}
+
+impl Display for RngSource {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", Into::<&str>::into(*self))
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum GenPassFormat {
+    Plain,
+    Json,
+    Csv,
+    Env,
+}
+
+fn parse_genpass_format(format: &str) -> anyhow::Result<GenPassFormat, anyhow::Error> {
+    format.parse()
+}
+
+impl From<GenPassFormat> for &'static str {
+    fn from(format: GenPassFormat) -> Self {
+        match format {
+            GenPassFormat::Plain => "plain",
+            GenPassFormat::Json => "json",
+            GenPassFormat::Csv => "csv",
+            GenPassFormat::Env => "env",
+        }
+    }
+}
+
+impl FromStr for GenPassFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "plain" => Ok(GenPassFormat::Plain),
+            "json" => Ok(GenPassFormat::Json),
+            "csv" => Ok(GenPassFormat::Csv),
+            "env" => Ok(GenPassFormat::Env),
+            _ => Err(anyhow::anyhow!("Invalid format")),
+        }
+    }
+}
+
+impl Display for GenPassFormat {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", Into::<&str>::into(*self))
+    }
+}
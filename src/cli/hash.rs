@@ -0,0 +1,63 @@
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+use clap::Parser;
+
+use super::{verify_file, Encoding};
+
+#[derive(Debug, Parser)]
+pub struct HashOpts {
+    #[arg(short, long, value_parser = verify_file, default_value = "-")]
+    pub input: String,
+
+    #[arg(long, value_parser = parse_hash_algorithm, default_value = "blake3")]
+    pub algorithm: HashAlgorithm,
+
+    // 输出 multihash（<code><len><digest>），并使用 multibase 编码，默认 base58btc
+    #[arg(long)]
+    pub multihash: bool,
+
+    // 摘要的编码方式，默认 hex，--multihash 时默认 base58
+    #[arg(long)]
+    pub encoding: Option<Encoding>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum HashAlgorithm {
+    Blake3,
+    Sha256,
+    Sha512,
+}
+
+fn parse_hash_algorithm(algorithm: &str) -> anyhow::Result<HashAlgorithm, anyhow::Error> {
+    algorithm.parse()
+}
+
+impl From<HashAlgorithm> for &'static str {
+    fn from(algorithm: HashAlgorithm) -> Self {
+        match algorithm {
+            HashAlgorithm::Blake3 => "blake3",
+            HashAlgorithm::Sha256 => "sha256",
+            HashAlgorithm::Sha512 => "sha512",
+        }
+    }
+}
+
+impl FromStr for HashAlgorithm {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "blake3" => Ok(HashAlgorithm::Blake3),
+            "sha256" => Ok(HashAlgorithm::Sha256),
+            "sha512" => Ok(HashAlgorithm::Sha512),
+            _ => Err(anyhow::anyhow!("Invalid hash algorithm")),
+        }
+    }
+}
+
+impl Display for HashAlgorithm {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", Into::<&str>::into(*self))
+    }
+}
@@ -0,0 +1,50 @@
+use clap::{Args, Parser};
+
+use super::verify_file;
+
+/// rcli hexdump，输出格式和 xxd 一致：偏移量、十六进制、ASCII 三列
+#[derive(Debug, Parser)]
+pub struct HexdumpOpts {
+    #[arg(short, long, value_parser = verify_file, default_value = "-")]
+    pub input: String,
+
+    // - 表示 stdout
+    #[arg(short, long, default_value = "-")]
+    pub output: String,
+
+    // 和 xxd -r 一样，把 hexdump 转回原始字节
+    #[arg(short, long)]
+    pub reverse: bool,
+
+    #[command(flatten)]
+    pub style: HexdumpStyle,
+}
+
+#[derive(Debug, Clone, Copy, Args)]
+pub struct HexdumpStyle {
+    // 每行的字节数，默认 16，--plain 时默认 30，和 xxd -c 一致
+    #[arg(short, long, value_parser = clap::value_parser!(u16).range(1..=256))]
+    pub cols: Option<u16>,
+
+    // 每组的字节数，组之间用空格分隔，0 表示不分组
+    #[arg(short, long, default_value_t = 2)]
+    pub group: u16,
+
+    // 只输出连续的十六进制，没有偏移量和 ASCII 列，和 xxd -p 一致
+    #[arg(short, long)]
+    pub plain: bool,
+
+    #[arg(short, long)]
+    pub uppercase: bool,
+}
+
+impl Default for HexdumpStyle {
+    fn default() -> Self {
+        Self {
+            cols: None,
+            group: 2,
+            plain: false,
+            uppercase: false,
+        }
+    }
+}
@@ -0,0 +1,155 @@
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+use clap::Parser;
+
+use super::verify_file;
+
+#[derive(Debug, Parser)]
+pub enum JwtSubCommand {
+    #[command(name = "decode", about = "Decode a JWT and show its header and claims")]
+    Decode(JwtDecodeOpts),
+
+    #[command(name = "sign", about = "Sign claims into a JWT with HS256 or EdDSA")]
+    Sign(JwtSignOpts),
+
+    #[command(
+        name = "verify",
+        about = "Verify a JWT signature, expiry, audience and issuer"
+    )]
+    Verify(JwtVerifyOpts),
+}
+
+#[derive(Debug, Parser)]
+pub struct JwtDecodeOpts {
+    // - 表示从 stdin 读取
+    #[arg(default_value = "-")]
+    pub token: String,
+}
+
+#[derive(Debug, Parser)]
+pub struct JwtSignOpts {
+    #[arg(long, value_parser = parse_jwt_algorithm, default_value = "HS256")]
+    pub alg: JwtAlgorithm,
+
+    // HS256 为共享的 secret 文件，EdDSA 为 text generate 生成的 ed25519.sk
+    #[arg(short, long, value_parser = verify_file)]
+    pub key: String,
+
+    // JSON 对象格式的 claims 文件，不指定时为空对象
+    #[arg(long, value_parser = verify_file)]
+    pub claims: Option<String>,
+
+    // 有效期，例如 30s / 15m / 1h / 7d，会设置 iat 和 exp
+    #[arg(long, value_parser = parse_duration)]
+    pub exp: Option<u64>,
+
+    #[arg(long)]
+    pub iss: Option<String>,
+
+    #[arg(long)]
+    pub sub: Option<String>,
+
+    #[arg(long)]
+    pub aud: Option<String>,
+}
+
+#[derive(Debug, Parser)]
+pub struct JwtVerifyOpts {
+    #[arg(default_value = "-")]
+    pub token: String,
+
+    // 必须显式指定算法，不信任 header 中的 alg，防止算法混淆攻击
+    #[arg(long, value_parser = parse_jwt_algorithm, default_value = "HS256")]
+    pub alg: JwtAlgorithm,
+
+    // HS256 为共享的 secret 文件，EdDSA 为 ed25519.pk
+    #[arg(short, long, value_parser = verify_file)]
+    pub key: String,
+
+    #[arg(long)]
+    pub aud: Option<String>,
+
+    #[arg(long)]
+    pub iss: Option<String>,
+
+    // 检查 exp / nbf 时允许的时钟偏差，单位秒
+    #[arg(long, default_value_t = 60)]
+    pub leeway: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JwtAlgorithm {
+    HS256,
+    EdDSA,
+}
+
+fn parse_jwt_algorithm(alg: &str) -> anyhow::Result<JwtAlgorithm, anyhow::Error> {
+    alg.parse()
+}
+
+impl From<JwtAlgorithm> for &'static str {
+    fn from(alg: JwtAlgorithm) -> Self {
+        match alg {
+            JwtAlgorithm::HS256 => "HS256",
+            JwtAlgorithm::EdDSA => "EdDSA",
+        }
+    }
+}
+
+impl FromStr for JwtAlgorithm {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "HS256" => Ok(JwtAlgorithm::HS256),
+            "EdDSA" => Ok(JwtAlgorithm::EdDSA),
+            _ => Err(anyhow::anyhow!("Invalid JWT algorithm")),
+        }
+    }
+}
+
+impl Display for JwtAlgorithm {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", Into::<&str>::into(*self))
+    }
+}
+
+// 30s / 15m / 1h / 7d / 2w，不带单位时为秒
+fn parse_duration(s: &str) -> anyhow::Result<u64, anyhow::Error> {
+    let s = s.trim();
+    let (num, unit) = match s.find(|c: char| !c.is_ascii_digit()) {
+        Some(i) => s.split_at(i),
+        None => (s, "s"),
+    };
+    let num: u64 = num
+        .parse()
+        .map_err(|_| anyhow::anyhow!("Invalid duration: {}", s))?;
+    let unit = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        _ => anyhow::bail!("Invalid duration unit: {}", unit),
+    };
+
+    num.checked_mul(unit)
+        .ok_or_else(|| anyhow::anyhow!("Duration is too large: {}", s))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("90").unwrap(), 90);
+        assert_eq!(parse_duration("15m").unwrap(), 900);
+        assert_eq!(parse_duration("1h").unwrap(), 3600);
+        assert_eq!(parse_duration("7d").unwrap(), 604800);
+        assert!(parse_duration("1y").is_err());
+        assert!(parse_duration("h").is_err());
+        assert!(parse_duration("18446744073709551615w").is_err());
+    }
+}
@@ -1,17 +1,29 @@
 use clap::Parser;
 use std::path::{Path, PathBuf};
 
+pub use age::*;
 pub use base64::*;
 pub use csv::*;
 pub use genpass::*;
+pub use hash::*;
+pub use hexdump::*;
 pub use http::*;
+pub use jwt::*;
+pub use otp::*;
 pub use text::*;
+pub use url::*;
 
+mod age;
 mod base64;
 mod csv;
 mod genpass;
+mod hash;
+mod hexdump;
 mod http;
+mod jwt;
+mod otp;
 mod text;
+mod url;
 
 /// https://juejin.cn/post/7242623208825110586?searchId=20240726205358129C4D8536158F998172
 ///  clap 的使用方式
@@ -42,11 +54,50 @@ pub enum SubCommand {
     #[command(subcommand, about = "Base64 encode/decode")]
     Base64(Base64SubCommand),
 
+    #[command(
+        name = "encode",
+        about = "Encode data as base64/base58/base32/hex/z85/ascii85"
+    )]
+    Encode(EncodeOpts),
+
+    #[command(
+        name = "decode",
+        about = "Decode base64/base58/base32/hex/z85/ascii85 data"
+    )]
+    Decode(DecodeOpts),
+
+    #[command(
+        name = "hash",
+        about = "Hash data with blake3/sha256/sha512, optionally as multihash"
+    )]
+    Hash(HashOpts),
+
+    #[command(
+        name = "hexdump",
+        about = "Show data as xxd-style hexdump, or reverse it"
+    )]
+    Hexdump(HexdumpOpts),
+
+    #[command(subcommand, about = "URL percent encode/decode and parse")]
+    Url(UrlSubCommand),
+
+    #[command(subcommand, about = "HTML entity escape/unescape")]
+    Html(HtmlSubCommand),
+
     #[command(subcommand, about = "Text sign/verify")]
     Text(TextSubCommand),
 
+    #[command(subcommand, about = "age-compatible file encryption")]
+    Age(AgeSubCommand),
+
     #[command(subcommand, about = "HTTP server")]
     Http(HttpSubCommand),
+
+    #[command(subcommand, about = "JWT decode/sign/verify")]
+    Jwt(JwtSubCommand),
+
+    #[command(subcommand, about = "TOTP/HOTP secret generation and code computation")]
+    Otp(OtpSubCommand),
 }
 
 // &'static 生命周期和进程是一样的
@@ -80,4 +131,91 @@ mod tests {
         assert_eq!(verify_file("Cargo.toml"), Ok("Cargo.toml".into()));
         assert_eq!(verify_file("not-exist"), Err("File does not exist"));
     }
+
+    #[test]
+    fn test_genpass_rng_rejected_for_subcommands() {
+        // derive / pin / recovery-codes 不使用 --rng / --seed，不能被静默忽略
+        let rejected: [&[&str]; 3] = [
+            &["rcli", "genpass", "--rng", "chacha", "pin"],
+            &["rcli", "genpass", "--seed", "01", "recovery-codes"],
+            &[
+                "rcli", "genpass", "--seed", "01", "derive", "--site", "a", "--user", "b",
+            ],
+        ];
+        for args in rejected {
+            assert!(Opts::try_parse_from(args).is_err());
+        }
+        assert!(Opts::try_parse_from(["rcli", "genpass", "pin", "--seed", "01"]).is_err());
+        assert!(
+            Opts::try_parse_from(["rcli", "genpass", "--rng", "chacha", "--seed", "01"]).is_ok()
+        );
+    }
+
+    #[test]
+    fn test_base64_armor_rejects_format_options() {
+        for flag in [
+            &["--format", "urlSafe"][..],
+            &["--no-pad"],
+            &["--wrap", "76"],
+        ] {
+            for armor in [&["--pem", "DATA"][..], &["--data-uri"]] {
+                let args = ["rcli", "base64", "encode"].iter().chain(armor).chain(flag);
+                assert!(
+                    Opts::try_parse_from(args).is_err(),
+                    "{:?} {:?}",
+                    armor,
+                    flag
+                );
+            }
+        }
+        assert!(Opts::try_parse_from(["rcli", "base64", "encode", "--pem", "DATA"]).is_ok());
+    }
+
+    #[test]
+    fn test_x25519_only_for_generate() {
+        // x25519 只用于 age，sign / verify 在解析参数时就拒绝
+        let sign = [
+            "rcli",
+            "text",
+            "sign",
+            "-k",
+            "Cargo.toml",
+            "--format",
+            "x25519",
+        ];
+        assert!(Opts::try_parse_from(sign).is_err());
+        let verify = [
+            "rcli",
+            "text",
+            "verify",
+            "-k",
+            "Cargo.toml",
+            "--sig",
+            "AAAA",
+            "--format",
+            "x25519",
+        ];
+        assert!(Opts::try_parse_from(verify).is_err());
+        let generate = ["rcli", "text", "generate", "-o", ".", "--format", "x25519"];
+        assert!(Opts::try_parse_from(generate).is_ok());
+    }
+
+    #[test]
+    fn test_otp_code_secret_sources() {
+        // secret 可以不出现在命令行中，从文件或终端读取
+        assert!(Opts::try_parse_from(["rcli", "otp", "code"]).is_ok());
+        assert!(
+            Opts::try_parse_from(["rcli", "otp", "code", "--secret-file", "Cargo.toml"]).is_ok()
+        );
+        let both = [
+            "rcli",
+            "otp",
+            "code",
+            "--secret",
+            "JBSWY3DPEHPK3PXP",
+            "--secret-file",
+            "Cargo.toml",
+        ];
+        assert!(Opts::try_parse_from(both).is_err());
+    }
 }
@@ -0,0 +1,120 @@
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+use clap::Parser;
+
+use super::verify_file;
+
+#[derive(Debug, Parser)]
+pub enum OtpSubCommand {
+    #[command(
+        name = "new",
+        about = "Generate a TOTP/HOTP secret and an otpauth:// URI"
+    )]
+    New(OtpNewOpts),
+
+    #[command(
+        name = "code",
+        about = "Compute a TOTP (RFC 6238) or HOTP (RFC 4226) code"
+    )]
+    Code(OtpCodeOpts),
+}
+
+#[derive(Debug, Parser)]
+pub struct OtpNewOpts {
+    #[arg(long)]
+    pub issuer: String,
+
+    #[arg(long)]
+    pub account: String,
+
+    #[arg(long, value_parser = parse_otp_algorithm, default_value = "sha1")]
+    pub algorithm: OtpAlgorithm,
+
+    #[arg(long, default_value_t = 6)]
+    pub digits: u32,
+
+    #[arg(long, default_value_t = 30)]
+    pub period: u64,
+
+    // 指定 counter 时生成 HOTP，否则生成 TOTP
+    #[arg(long)]
+    pub counter: Option<u64>,
+
+    // secret 的字节数，RFC 4226 建议至少 160 bit
+    #[arg(long, default_value_t = 20)]
+    pub bytes: usize,
+
+    // 不在终端中输出二维码
+    #[arg(long)]
+    pub no_qr: bool,
+}
+
+#[derive(Debug, Parser)]
+pub struct OtpCodeOpts {
+    // base32 编码的 secret，忽略大小写、空格和 padding
+    // 命令行参数会出现在 shell history 和 ps 中，建议用 --secret-file 或在终端中输入
+    #[arg(long)]
+    pub secret: Option<String>,
+
+    // 不指定 --secret 和 --secret-file 时从 TTY 读取
+    #[arg(long, value_parser = verify_file, conflicts_with = "secret")]
+    pub secret_file: Option<String>,
+
+    #[arg(long, value_parser = parse_otp_algorithm, default_value = "sha1")]
+    pub algorithm: OtpAlgorithm,
+
+    #[arg(long, default_value_t = 6)]
+    pub digits: u32,
+
+    #[arg(long, default_value_t = 30)]
+    pub period: u64,
+
+    // 指定 counter 时计算 HOTP，否则计算 TOTP
+    #[arg(long, conflicts_with = "time")]
+    pub counter: Option<u64>,
+
+    // unix 时间戳，默认为当前时间
+    #[arg(long)]
+    pub time: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum OtpAlgorithm {
+    Sha1,
+    Sha256,
+    Sha512,
+}
+
+fn parse_otp_algorithm(algorithm: &str) -> anyhow::Result<OtpAlgorithm, anyhow::Error> {
+    algorithm.parse()
+}
+
+impl From<OtpAlgorithm> for &'static str {
+    fn from(algorithm: OtpAlgorithm) -> Self {
+        match algorithm {
+            OtpAlgorithm::Sha1 => "sha1",
+            OtpAlgorithm::Sha256 => "sha256",
+            OtpAlgorithm::Sha512 => "sha512",
+        }
+    }
+}
+
+impl FromStr for OtpAlgorithm {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "sha1" => Ok(OtpAlgorithm::Sha1),
+            "sha256" => Ok(OtpAlgorithm::Sha256),
+            "sha512" => Ok(OtpAlgorithm::Sha512),
+            _ => Err(anyhow::anyhow!("Invalid algorithm")),
+        }
+    }
+}
+
+impl Display for OtpAlgorithm {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", Into::<&str>::into(*self))
+    }
+}
@@ -2,7 +2,7 @@ use std::fmt::{Display, Formatter};
 use std::path::PathBuf;
 use std::str::FromStr;
 
-use clap::Parser;
+use clap::{Args, Parser};
 
 use super::{verify_file, verify_path};
 
@@ -21,8 +21,35 @@ pub enum TextSubCommand {
     )]
     Verify(TextVerifyOpts),
 
-    #[command(about = "Generate a random blake3 key or ed25519 key pair")]
+    #[command(about = "Generate a random blake3 key, ed25519 key pair or age x25519 identity")]
     Generate(KeyGenerateOpts),
+
+    #[command(
+        name = "encrypt",
+        about = "Encrypt a file with ChaCha20-Poly1305 using a key file or passphrase"
+    )]
+    Encrypt(TextEncryptOpts),
+
+    #[command(name = "decrypt", about = "Decrypt a file produced by text encrypt")]
+    Decrypt(TextDecryptOpts),
+
+    #[command(
+        name = "keyinfo",
+        about = "Show the type, encoding and entropy of a key file"
+    )]
+    KeyInfo(KeyInfoOpts),
+
+    #[command(
+        name = "sign-manifest",
+        about = "Hash every file in a directory with blake3 and sign the manifest with ed25519"
+    )]
+    SignManifest(ManifestOpts),
+
+    #[command(
+        name = "verify-manifest",
+        about = "Verify a signed manifest and report missing, extra and modified files"
+    )]
+    VerifyManifest(ManifestOpts),
 }
 
 #[derive(Debug, Parser)]
@@ -35,8 +62,23 @@ pub struct TextSignOpts {
     #[arg(short, long, value_parser = verify_file)]
     pub key: String,
 
-    #[arg(long, default_value = "blake3", value_parser = parse_format)]
-    pub format: TextSignFormat,
+    // 默认 blake3，指定 --sig-file 时默认 ed25519
+    #[arg(long, value_parser = parse_format)]
+    pub format: Option<TextSignFormat>,
+
+    // 写入 minisign 格式的签名文件，只支持 ed25519
+    #[arg(short = 'o', long)]
+    pub sig_file: Option<String>,
+
+    // 写入签名文件并被签名保护的注释，默认为时间戳和文件名
+    #[arg(short, long, requires = "sig_file")]
+    pub trusted_comment: Option<String>,
+}
+
+impl TextSignOpts {
+    pub fn format(&self) -> TextSignFormat {
+        default_sign_format(self.format, self.sig_file.is_some())
+    }
 }
 
 #[derive(Debug, Parser)]
@@ -47,20 +89,147 @@ pub struct TextVerifyOpts {
     #[arg(short, long, value_parser = verify_file)]
     pub key: String,
 
-    #[arg(long)]
-    pub sig: String,
+    #[arg(long, required_unless_present = "sig_file")]
+    pub sig: Option<String>,
+
+    // minisign 格式的签名文件（.minisig），key 可以是 minisign 公钥
+    #[arg(long, value_parser = verify_file, conflicts_with = "sig")]
+    pub sig_file: Option<String>,
 
-    #[arg(long, default_value = "blake3", value_parser = parse_format)]
-    pub format: TextSignFormat,
+    #[arg(long, value_parser = parse_format)]
+    pub format: Option<TextSignFormat>,
+}
+
+impl TextVerifyOpts {
+    pub fn format(&self) -> TextSignFormat {
+        default_sign_format(self.format, self.sig_file.is_some())
+    }
 }
 
 #[derive(Debug, Parser)]
 pub struct KeyGenerateOpts {
-    #[arg(long, default_value = "blake3", value_parser = parse_format)]
-    pub format: TextSignFormat,
+    #[arg(long, default_value = "blake3", value_parser = parse_key_type)]
+    pub format: TextKeyType,
 
     #[arg(short, long, value_parser = verify_path)]
     pub output_path: PathBuf,
+
+    // 只对 ed25519 有效，读取时会自动识别格式
+    #[arg(long, default_value = "raw", value_parser = parse_key_format)]
+    pub key_format: KeyFormat,
+
+    // 用口令加密私钥文件，在终端中输入两次
+    #[arg(long)]
+    pub passphrase: bool,
+
+    #[arg(long, value_parser = verify_file, conflicts_with = "passphrase")]
+    pub passphrase_file: Option<String>,
+
+    // 默认不覆盖已经存在的密钥文件
+    #[arg(long)]
+    pub force: bool,
+}
+
+#[derive(Debug, Parser)]
+pub struct KeyInfoOpts {
+    #[arg(short, long, value_parser = verify_file)]
+    pub key: String,
+}
+
+#[derive(Debug, Parser)]
+pub struct ManifestOpts {
+    #[arg(value_parser = verify_path)]
+    pub dir: PathBuf,
+
+    // 签名时为 ed25519 私钥，验证时为公钥
+    #[arg(short, long, value_parser = verify_file)]
+    pub key: String,
+
+    // 默认为 <dir>/MANIFEST.b3，签名写入同名的 .sig 文件
+    #[arg(short, long)]
+    pub manifest: Option<PathBuf>,
+}
+
+#[derive(Debug, Parser)]
+pub struct TextEncryptOpts {
+    #[arg(short, long, value_parser = verify_file, default_value = "-")]
+    pub input: String,
+
+    // - 表示 stdout，密文是二进制数据
+    #[arg(short, long, default_value = "-")]
+    pub output: String,
+
+    #[arg(long, value_parser = parse_cipher, default_value = "xchacha20poly1305")]
+    pub cipher: TextCipher,
+
+    #[command(flatten)]
+    pub secret: EncryptionSecret,
+}
+
+#[derive(Debug, Parser)]
+pub struct TextDecryptOpts {
+    #[arg(short, long, value_parser = verify_file, default_value = "-")]
+    pub input: String,
+
+    #[arg(short, long, default_value = "-")]
+    pub output: String,
+
+    #[command(flatten)]
+    pub secret: EncryptionSecret,
+}
+
+/// 三选一：32 字节的 key 文件（text generate 生成的 blake3.txt），或者经过 Argon2id 的口令
+#[derive(Debug, Clone, Args)]
+#[group(required = true, multiple = false)]
+pub struct EncryptionSecret {
+    #[arg(short, long, value_parser = verify_file)]
+    pub key: Option<String>,
+
+    // 在终端中输入口令
+    #[arg(long)]
+    pub passphrase: bool,
+
+    // 从文件读取口令，去掉末尾的换行
+    #[arg(long, value_parser = verify_file)]
+    pub passphrase_file: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TextCipher {
+    ChaCha20Poly1305,
+    // 24 字节的 nonce，随机生成 nonce 不用担心碰撞
+    XChaCha20Poly1305,
+}
+
+fn parse_cipher(cipher: &str) -> anyhow::Result<TextCipher, anyhow::Error> {
+    cipher.parse()
+}
+
+impl From<TextCipher> for &'static str {
+    fn from(cipher: TextCipher) -> Self {
+        match cipher {
+            TextCipher::ChaCha20Poly1305 => "chacha20poly1305",
+            TextCipher::XChaCha20Poly1305 => "xchacha20poly1305",
+        }
+    }
+}
+
+impl FromStr for TextCipher {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "chacha20poly1305" => Ok(TextCipher::ChaCha20Poly1305),
+            "xchacha20poly1305" => Ok(TextCipher::XChaCha20Poly1305),
+            _ => Err(anyhow::anyhow!("Invalid cipher")),
+        }
+    }
+}
+
+impl Display for TextCipher {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", Into::<&str>::into(*self))
+    }
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -69,6 +238,15 @@ pub enum TextSignFormat {
     Ed25519,
 }
 
+// 签名文件只支持 ed25519，不需要再写 --format
+fn default_sign_format(format: Option<TextSignFormat>, sig_file: bool) -> TextSignFormat {
+    match format {
+        Some(format) => format,
+        None if sig_file => TextSignFormat::Ed25519,
+        None => TextSignFormat::Blake3,
+    }
+}
+
 fn parse_format(format: &str) -> anyhow::Result<TextSignFormat, anyhow::Error> {
     format.parse()
 }
@@ -101,3 +279,97 @@ impl Display for TextSignFormat {
         write!(f, "{}", Into::<&str>::into(*self))
     }
 }
+
+/// text generate 生成的密钥类型，比签名格式多了 x25519
+#[derive(Clone, Copy, Debug)]
+pub enum TextKeyType {
+    Blake3,
+    Ed25519,
+    // age 的 X25519 identity，只用于 age 加解密，不能签名
+    X25519,
+}
+
+fn parse_key_type(key_type: &str) -> anyhow::Result<TextKeyType, anyhow::Error> {
+    key_type.parse()
+}
+
+impl From<TextKeyType> for &'static str {
+    fn from(key_type: TextKeyType) -> Self {
+        match key_type {
+            TextKeyType::Blake3 => "blake3",
+            TextKeyType::Ed25519 => "ed25519",
+            TextKeyType::X25519 => "x25519",
+        }
+    }
+}
+
+impl FromStr for TextKeyType {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "blake3" => Ok(TextKeyType::Blake3),
+            "ed25519" => Ok(TextKeyType::Ed25519),
+            "x25519" => Ok(TextKeyType::X25519),
+            _ => Err(anyhow::anyhow!("Invalid key type")),
+        }
+    }
+}
+
+impl Display for TextKeyType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", Into::<&str>::into(*self))
+    }
+}
+
+/// ed25519 密钥文件的格式
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KeyFormat {
+    // 32 字节的原始数据
+    Raw,
+    // PKCS#8 私钥 / SPKI 公钥，可以被 openssl 读取
+    Pem,
+    // ssh-keygen 的私钥文件和 ssh-ed25519 公钥
+    OpenSsh,
+    // RFC 8037 的 OKP JWK
+    Jwk,
+    // minisign 的公私钥文件，key id 随机生成
+    Minisign,
+}
+
+fn parse_key_format(format: &str) -> anyhow::Result<KeyFormat, anyhow::Error> {
+    format.parse()
+}
+
+impl From<KeyFormat> for &'static str {
+    fn from(format: KeyFormat) -> Self {
+        match format {
+            KeyFormat::Raw => "raw",
+            KeyFormat::Pem => "pem",
+            KeyFormat::OpenSsh => "openssh",
+            KeyFormat::Jwk => "jwk",
+            KeyFormat::Minisign => "minisign",
+        }
+    }
+}
+
+impl FromStr for KeyFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "raw" => Ok(KeyFormat::Raw),
+            "pem" => Ok(KeyFormat::Pem),
+            "openssh" | "ssh" => Ok(KeyFormat::OpenSsh),
+            "jwk" => Ok(KeyFormat::Jwk),
+            "minisign" => Ok(KeyFormat::Minisign),
+            _ => Err(anyhow::anyhow!("Invalid key format")),
+        }
+    }
+}
+
+impl Display for KeyFormat {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", Into::<&str>::into(*self))
+    }
+}
@@ -0,0 +1,102 @@
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+use clap::Parser;
+
+use super::verify_file;
+
+#[derive(Debug, Parser)]
+pub enum UrlSubCommand {
+    #[command(
+        name = "encode",
+        about = "Percent-encode a URL, URL component or form value"
+    )]
+    Encode(UrlCodecOpts),
+
+    #[command(name = "decode", about = "Decode a percent-encoded string")]
+    Decode(UrlCodecOpts),
+
+    #[command(name = "parse", about = "Parse a URL and print its parts as JSON")]
+    Parse(UrlParseOpts),
+}
+
+#[derive(Debug, Parser)]
+pub struct UrlCodecOpts {
+    #[arg(short, long, value_parser = verify_file, default_value = "-")]
+    pub input: String,
+
+    // - 表示 stdout
+    #[arg(short, long, default_value = "-")]
+    pub output: String,
+
+    #[arg(long, value_parser = parse_url_mode, default_value = "component")]
+    pub mode: UrlMode,
+}
+
+#[derive(Debug, Parser)]
+pub struct UrlParseOpts {
+    #[arg(short, long, value_parser = verify_file, default_value = "-")]
+    pub input: String,
+}
+
+#[derive(Debug, Parser)]
+pub enum HtmlSubCommand {
+    #[command(name = "escape", about = "Escape & < > \" ' as HTML entities")]
+    Escape(HtmlOpts),
+
+    #[command(name = "unescape", about = "Unescape named and numeric HTML entities")]
+    Unescape(HtmlOpts),
+}
+
+#[derive(Debug, Parser)]
+pub struct HtmlOpts {
+    #[arg(short, long, value_parser = verify_file, default_value = "-")]
+    pub input: String,
+
+    // - 表示 stdout
+    #[arg(short, long, default_value = "-")]
+    pub output: String,
+}
+
+/// component 对应 encodeURIComponent，只保留 unreserved 字符
+/// full 对应 encodeURI，保留 : / ? # 等 URL 的分隔符
+/// form 为 application/x-www-form-urlencoded，空格编码为 +
+#[derive(Debug, Clone, Copy)]
+pub enum UrlMode {
+    Component,
+    Full,
+    Form,
+}
+
+fn parse_url_mode(mode: &str) -> anyhow::Result<UrlMode, anyhow::Error> {
+    mode.parse()
+}
+
+impl From<UrlMode> for &'static str {
+    fn from(mode: UrlMode) -> Self {
+        match mode {
+            UrlMode::Component => "component",
+            UrlMode::Full => "full",
+            UrlMode::Form => "form",
+        }
+    }
+}
+
+impl FromStr for UrlMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "component" => Ok(UrlMode::Component),
+            "full" => Ok(UrlMode::Full),
+            "form" => Ok(UrlMode::Form),
+            _ => Err(anyhow::anyhow!("Invalid url mode")),
+        }
+    }
+}
+
+impl Display for UrlMode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", Into::<&str>::into(*self))
+    }
+}
@@ -1,12 +1,21 @@
-use std::fs;
-
 use clap::Parser;
-use zxcvbn::zxcvbn;
+use zeroize::Zeroizing;
 
 use rcli::{
-    process_csv, process_decode, process_encode, process_genpass, process_http_server,
-    process_text_key_generate, process_text_sign, process_text_verify, Base64SubCommand,
-    HttpSubCommand, Opts, SubCommand, TextSubCommand,
+    build_rng, describe_jwt_times, explain_genpass, format_passwords, process_age_decrypt,
+    process_age_encrypt, process_csv, process_decode, process_decode_auto, process_encode,
+    process_encode_data_uri, process_encode_pem, process_genpass_batch, process_genpass_derive,
+    process_genpass_pin, process_hash_encoded, process_hexdump, process_hexdump_reverse,
+    process_html_escape, process_html_unescape, process_http_server, process_jwt_decode,
+    process_jwt_sign, process_jwt_verify, process_otp_code, process_otp_new,
+    process_recovery_codes, process_text_decrypt, process_text_encrypt, process_text_key_generate,
+    process_text_keyinfo, process_text_sign, process_text_sign_detached,
+    process_text_sign_manifest, process_text_verify, process_text_verify_detached,
+    process_text_verify_manifest, process_url_decode, process_url_encode, process_url_parse,
+    read_new_secret, read_secret, read_token, render_qr, write_key_files, write_secret_file,
+    AgeSubCommand, Base64SubCommand, GenPassFormat, GenPassSubCommand, HtmlSubCommand,
+    HttpSubCommand, JwtSubCommand, JwtValidation, Opts, OtpSubCommand, RngSource, SubCommand,
+    TextSubCommand, UrlSubCommand,
 };
 
 // anyhow 实现了 大多数 standard 的转换
@@ -16,8 +25,6 @@ use rcli::{
 async fn main() -> anyhow::Result<()> {
     let opts = Opts::parse();
 
-    println!("{:?}", opts);
-
     match opts.cmd {
         // 使用到 opts 中的数据结构，必须是 pub 的
         SubCommand::Csv(opts) => {
@@ -32,60 +39,310 @@ async fn main() -> anyhow::Result<()> {
         }
 
         SubCommand::GenPass(opts) => {
-            let password = process_genpass(
-                opts.length,
-                opts.uppercase,
-                opts.lowercase,
-                opts.number,
-                opts.symbol,
-            )?;
-
-            println!("password = {}", password);
-
-            // output password strength in stderr
-            let estimate = zxcvbn(&password, &[])?;
-            eprintln!("Password strength: {}", estimate.score());
+            match opts.cmd {
+                Some(GenPassSubCommand::Derive(opts)) => {
+                    let master = read_secret(opts.master_file.as_deref(), "Master secret: ")?;
+                    let password = process_genpass_derive(
+                        &master,
+                        &opts.site,
+                        &opts.user,
+                        opts.counter,
+                        &opts.policy,
+                    )?;
+                    println!("{}", *password.value);
+                }
+
+                Some(GenPassSubCommand::Pin(opts)) => {
+                    let pin = process_genpass_pin(opts.length)?;
+                    println!("{}", pin);
+                }
+
+                Some(GenPassSubCommand::RecoveryCodes(opts)) => {
+                    let codes = process_recovery_codes(opts.count, opts.groups)?;
+                    for code in codes {
+                        println!("{}", code);
+                    }
+                }
+
+                None => {
+                    let mut rng = build_rng(opts.rng, opts.seed.as_deref())?;
+                    if let RngSource::ChaCha = opts.rng {
+                        eprintln!("WARNING: seeded ChaCha20 is for tests only, passwords are reproducible");
+                    }
+                    if opts.explain {
+                        eprintln!(
+                            "{}",
+                            explain_genpass(&opts.policy, opts.pronounceable, opts.rng)?
+                        );
+                    }
+
+                    let passwords = process_genpass_batch(
+                        opts.count,
+                        &opts.label,
+                        &opts.policy,
+                        opts.pronounceable,
+                        &mut rng,
+                    )?;
+                    let content = format_passwords(&passwords, opts.format)?;
+
+                    match opts.output {
+                        Some(output) => write_secret_file(output, content.as_bytes(), true)?,
+                        None => print!("{}", *content),
+                    }
+
+                    // plain 格式中没有强度信息，output password strength in stderr
+                    if let GenPassFormat::Plain = opts.format {
+                        for p in &passwords {
+                            eprintln!(
+                                "{} strength: {}, entropy: {} bits",
+                                p.label, p.strength, p.entropy_bits
+                            );
+                        }
+                    }
+                }
+            }
         }
 
         SubCommand::Base64(subcmd) => match subcmd {
             Base64SubCommand::Encode(opts) => {
-                let encode = process_encode(&opts.input, opts.format)?;
-                println!("encode = {}", encode);
+                if opts.data_uri {
+                    process_encode_data_uri(&opts.input, &opts.output)?;
+                } else if let Some(label) = &opts.pem {
+                    process_encode_pem(&opts.input, &opts.output, label)?;
+                } else {
+                    process_encode(&opts.input, &opts.output, opts.format.into(), &opts.style)?;
+                }
             }
 
             Base64SubCommand::Decode(opts) => {
-                let decode = process_decode(&opts.input, opts.format)?;
-                let decode = String::from_utf8(decode)?;
+                process_decode(&opts.input, &opts.output, opts.format.into(), &opts.style)?;
+            }
+        },
+
+        SubCommand::Encode(opts) => {
+            process_encode(&opts.input, &opts.output, opts.encoding, &opts.style)?;
+        }
+
+        SubCommand::Decode(opts) => {
+            // 解码后的数据可能是二进制，直接输出原始字节
+            if opts.auto {
+                let decoded = process_decode_auto(&opts.input, &opts.output, opts.style.hexdump)?;
+                eprintln!("detected encoding: {}", decoded.encoding);
+                if !decoded.alternatives.is_empty() {
+                    let alternatives: Vec<String> =
+                        decoded.alternatives.iter().map(|e| e.to_string()).collect();
+                    eprintln!("ambiguous, also decodes as: {}", alternatives.join(", "));
+                }
+            } else {
+                process_decode(&opts.input, &opts.output, opts.encoding, &opts.style)?;
+            }
+        }
+
+        SubCommand::Hash(opts) => {
+            let digest =
+                process_hash_encoded(&opts.input, opts.algorithm, opts.multihash, opts.encoding)?;
+            println!("{}", digest);
+        }
+
+        SubCommand::Hexdump(opts) => {
+            if opts.reverse {
+                process_hexdump_reverse(&opts.input, &opts.output, &opts.style)?;
+            } else {
+                process_hexdump(&opts.input, &opts.output, &opts.style)?;
+            }
+        }
+
+        SubCommand::Url(cmd) => match cmd {
+            UrlSubCommand::Encode(opts) => {
+                process_url_encode(&opts.input, &opts.output, opts.mode)?;
+            }
+
+            UrlSubCommand::Decode(opts) => {
+                process_url_decode(&opts.input, &opts.output, opts.mode)?;
+            }
 
-                println!("decode = {}", decode);
+            UrlSubCommand::Parse(opts) => {
+                println!("{}", process_url_parse(&opts.input)?);
             }
         },
 
+        SubCommand::Html(cmd) => match cmd {
+            HtmlSubCommand::Escape(opts) => process_html_escape(&opts.input, &opts.output)?,
+            HtmlSubCommand::Unescape(opts) => process_html_unescape(&opts.input, &opts.output)?,
+        },
+
         SubCommand::Text(subcmd) => match subcmd {
-            TextSubCommand::Sign(opts) => {
-                let signature = process_text_sign(&opts.input, &opts.key, opts.format)?;
-                println!("signed = {}", signature);
+            TextSubCommand::Sign(opts) => match &opts.sig_file {
+                Some(sig_file) => {
+                    process_text_sign_detached(
+                        &opts.input,
+                        &opts.key,
+                        opts.format(),
+                        sig_file,
+                        opts.trusted_comment.as_deref(),
+                    )?;
+                    println!("signature written to {}", sig_file);
+                }
+                None => {
+                    let signature = process_text_sign(&opts.input, &opts.key, opts.format())?;
+                    println!("signed = {}", signature);
+                }
+            },
+
+            // 验证失败时返回错误，进程退出码为 1
+            TextSubCommand::Verify(opts) => match (&opts.sig_file, &opts.sig) {
+                (Some(sig_file), _) => {
+                    let trusted_comment = process_text_verify_detached(
+                        &opts.input,
+                        &opts.key,
+                        opts.format(),
+                        sig_file,
+                    )?;
+                    println!("verified");
+                    println!("trusted comment: {}", trusted_comment);
+                }
+                (None, sig) => {
+                    // clap 保证 --sig 和 --sig-file 至少有一个
+                    let sig = sig.as_deref().unwrap_or_default();
+                    process_text_verify(&opts.input, &opts.key, opts.format(), sig)?;
+                    println!("verified");
+                }
+            },
+
+            TextSubCommand::Encrypt(opts) => {
+                process_text_encrypt(&opts.input, &opts.output, opts.cipher, &opts.secret)?;
             }
 
-            TextSubCommand::Verify(opts) => {
-                let verified = process_text_verify(&opts.input, &opts.key, opts.format, &opts.sig)?;
-                println!("verified = {}", verified);
+            TextSubCommand::Decrypt(opts) => {
+                process_text_decrypt(&opts.input, &opts.output, &opts.secret)?;
             }
 
             TextSubCommand::Generate(opts) => {
-                let key_map = process_text_key_generate(opts.format)?;
-                for (key, value) in key_map {
-                    fs::write(opts.output_path.join(key), value)?;
+                let passphrase = match (opts.passphrase, opts.passphrase_file.as_deref()) {
+                    (false, None) => None,
+                    (_, path) => Some(read_new_secret(path, "Passphrase: ")?),
+                };
+                let key_map = process_text_key_generate(
+                    opts.format,
+                    opts.key_format,
+                    passphrase.as_deref().map(|p| p.as_slice()),
+                )?;
+                write_key_files(&opts.output_path, &key_map, opts.force)?;
+            }
+
+            TextSubCommand::KeyInfo(opts) => {
+                println!("{}", process_text_keyinfo(&opts.key)?);
+            }
+
+            TextSubCommand::SignManifest(opts) => {
+                let (manifest, count) =
+                    process_text_sign_manifest(&opts.dir, &opts.key, opts.manifest.as_deref())?;
+                println!("{} files written to {}", count, manifest.display());
+            }
+
+            // 有文件不一致时先输出报告，再以错误退出
+            TextSubCommand::VerifyManifest(opts) => {
+                let report =
+                    process_text_verify_manifest(&opts.dir, &opts.key, opts.manifest.as_deref())?;
+                println!("{}", report);
+                if !report.is_clean() {
+                    anyhow::bail!("Directory does not match the manifest");
                 }
             }
         },
 
+        SubCommand::Age(cmd) => match cmd {
+            AgeSubCommand::Encrypt(opts) => {
+                process_age_encrypt(&opts.input, &opts.output, &opts.recipients, opts.passphrase)?;
+            }
+
+            AgeSubCommand::Decrypt(opts) => {
+                process_age_decrypt(&opts.input, &opts.output, &opts.identities)?;
+            }
+        },
+
         SubCommand::Http(cmd) => match cmd {
             HttpSubCommand::Serve(opts) => {
                 println!("Serving at http://0.0.0.0:{}", opts.port);
                 process_http_server(opts.dir, opts.port).await?;
             }
         },
+
+        SubCommand::Jwt(cmd) => match cmd {
+            JwtSubCommand::Decode(opts) => {
+                let jwt = process_jwt_decode(&read_token(&opts.token)?)?;
+                println!("{}", serde_json::to_string_pretty(&jwt.header)?);
+                println!("{}", serde_json::to_string_pretty(&jwt.claims)?);
+                for line in describe_jwt_times(&jwt.claims) {
+                    println!("{}", line);
+                }
+            }
+
+            JwtSubCommand::Sign(opts) => {
+                let token = process_jwt_sign(
+                    opts.alg,
+                    &opts.key,
+                    opts.claims.as_deref(),
+                    opts.exp,
+                    &[
+                        ("iss", opts.iss.as_deref()),
+                        ("sub", opts.sub.as_deref()),
+                        ("aud", opts.aud.as_deref()),
+                    ],
+                )?;
+                println!("{}", token);
+            }
+
+            JwtSubCommand::Verify(opts) => {
+                let validation = JwtValidation {
+                    aud: opts.aud.as_deref(),
+                    iss: opts.iss.as_deref(),
+                    leeway: opts.leeway,
+                };
+                let claims = process_jwt_verify(
+                    &read_token(&opts.token)?,
+                    opts.alg,
+                    &opts.key,
+                    &validation,
+                )?;
+                println!("{}", serde_json::to_string_pretty(&claims)?);
+            }
+        },
+
+        SubCommand::Otp(cmd) => match cmd {
+            OtpSubCommand::New(opts) => {
+                let otp = process_otp_new(
+                    &opts.issuer,
+                    &opts.account,
+                    opts.algorithm,
+                    opts.digits,
+                    opts.period,
+                    opts.counter,
+                    opts.bytes,
+                )?;
+                println!("secret = {}", otp.secret);
+                println!("uri = {}", otp.uri);
+                if !opts.no_qr {
+                    println!("{}", render_qr(&otp.uri)?);
+                }
+            }
+
+            OtpSubCommand::Code(opts) => {
+                let secret = match opts.secret {
+                    Some(secret) => Zeroizing::new(secret.into_bytes()),
+                    None => read_secret(opts.secret_file.as_deref(), "Secret: ")?,
+                };
+                let code = process_otp_code(
+                    std::str::from_utf8(&secret)?,
+                    opts.algorithm,
+                    opts.digits,
+                    opts.period,
+                    opts.counter,
+                    opts.time,
+                )?;
+                println!("{}", code);
+            }
+        },
     }
 
     Ok(())
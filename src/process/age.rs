@@ -0,0 +1,734 @@
+use std::fs;
+use std::io::{BufRead, BufReader, Read, Write};
+
+use anyhow::Result;
+use base64::engine::general_purpose::STANDARD_NO_PAD;
+use base64::Engine;
+use bech32::{Bech32, Hrp};
+use chacha20poly1305::aead::{Aead, KeyInit};
+use chacha20poly1305::ChaCha20Poly1305;
+use hkdf::Hkdf;
+use hmac::{Hmac, Mac};
+use rand::rngs::OsRng;
+use rand::RngCore;
+use sha2::Sha256;
+use x25519_dalek::{PublicKey, StaticSecret};
+use zeroize::Zeroizing;
+
+use super::encrypt::read_full;
+use super::key_protect::read_key_file;
+use crate::{get_reader, get_writer, read_new_secret, read_secret};
+
+/// age v1 格式，规范见 https://age-encryption.org/v1
+///
+/// header 为文本：版本行、若干个 stanza（每个 recipient 一个，包装同一个 file key）、HMAC；
+/// payload 为 16 字节 nonce + 64 KiB 一块的 ChaCha20-Poly1305 STREAM
+const INTRO: &str = "age-encryption.org/v1";
+const X25519_LABEL: &[u8] = b"age-encryption.org/v1/X25519";
+const SCRYPT_LABEL: &[u8] = b"age-encryption.org/v1/scrypt";
+const RECIPIENT_HRP: &str = "age";
+const IDENTITY_HRP: &str = "age-secret-key-";
+
+const FILE_KEY_SIZE: usize = 16;
+const CHUNK_SIZE: usize = 64 * 1024;
+const TAG_SIZE: usize = 16;
+// stanza body 每行 64 个字符
+const COLUMNS: usize = 64;
+// 和 age 的默认值一致，解密时限制上限，防止恶意文件消耗大量 CPU 和内存
+const SCRYPT_LOG_N: u8 = 18;
+const SCRYPT_MAX_LOG_N: u8 = 22;
+
+type FileKey = Zeroizing<[u8; FILE_KEY_SIZE]>;
+
+#[derive(Debug, PartialEq, Eq)]
+struct Stanza {
+    tag: String,
+    args: Vec<String>,
+    body: Vec<u8>,
+}
+
+/// age 的 X25519 identity，对应 AGE-SECRET-KEY-1... 字符串
+pub struct AgeIdentity {
+    secret: StaticSecret,
+}
+
+impl AgeIdentity {
+    pub fn generate() -> Self {
+        Self {
+            secret: StaticSecret::random_from_rng(OsRng),
+        }
+    }
+
+    pub fn parse(s: &str) -> Result<Self> {
+        let key = Zeroizing::new(bech32_decode(s, IDENTITY_HRP)?);
+        Ok(Self {
+            secret: StaticSecret::from(*key),
+        })
+    }
+
+    pub fn to_secret_string(&self) -> Zeroizing<String> {
+        let hrp = Hrp::parse_unchecked(IDENTITY_HRP);
+        let encoded = bech32::encode_upper::<Bech32>(hrp, self.secret.as_bytes())
+            .expect("32 bytes always fit in bech32");
+        Zeroizing::new(encoded)
+    }
+
+    pub fn recipient(&self) -> String {
+        encode_recipient(&PublicKey::from(&self.secret))
+    }
+}
+
+/// 和 age-keygen 的输出格式一致
+pub fn process_age_keygen() -> Result<(Zeroizing<String>, String)> {
+    let identity = AgeIdentity::generate();
+    let recipient = identity.recipient();
+    let created = chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true);
+    let content = format!(
+        "# created: {}\n# public key: {}\n{}\n",
+        created,
+        recipient,
+        *identity.to_secret_string()
+    );
+
+    Ok((Zeroizing::new(content), recipient))
+}
+
+pub fn process_age_encrypt(
+    input: &str,
+    output: &str,
+    recipients: &[String],
+    passphrase: bool,
+) -> Result<()> {
+    let file_key = random_file_key();
+    let stanzas = if passphrase {
+        let passphrase = read_new_secret(None, "Passphrase: ")?;
+        vec![wrap_scrypt(&file_key, &passphrase, SCRYPT_LOG_N)?]
+    } else {
+        recipients
+            .iter()
+            .map(|r| wrap_x25519(&file_key, &parse_recipient(r)?))
+            .collect::<Result<Vec<_>>>()?
+    };
+
+    let mut reader = get_reader(input)?;
+    let mut writer = get_writer(output)?;
+    encrypt_with_stanzas(&mut reader, &mut writer, &file_key, &stanzas)?;
+    writer.flush()?;
+
+    Ok(())
+}
+
+pub fn process_age_decrypt(input: &str, output: &str, identity_files: &[String]) -> Result<()> {
+    let mut identities = Vec::new();
+    for path in identity_files {
+        identities.extend(read_identity_file(path)?);
+    }
+
+    let mut reader = BufReader::new(get_reader(input)?);
+    let mut writer = get_writer(output)?;
+    let ret = decrypt_stream(&mut reader, &mut writer, &identities, || {
+        read_secret(None, "Passphrase: ")
+    })
+    .and_then(|_| {
+        writer.flush()?;
+        Ok(())
+    });
+    // 和 text decrypt 一样，解密失败时不留下只有一部分的明文文件
+    if ret.is_err() && output != "-" {
+        drop(writer);
+        let _ = fs::remove_file(output);
+    }
+
+    ret
+}
+
+/// 忽略空行和 # 开头的注释，兼容 age-keygen 生成的文件
+pub fn read_identity_file(path: &str) -> Result<Vec<AgeIdentity>> {
+    // 和 text generate --passphrase 生成的 x25519.sk 兼容
+    let content = Zeroizing::new(String::from_utf8(read_key_file(path)?.to_vec())?);
+    let identities = content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(AgeIdentity::parse)
+        .collect::<Result<Vec<_>>>()?;
+    if identities.is_empty() {
+        anyhow::bail!("No identities found in {}", path);
+    }
+
+    Ok(identities)
+}
+
+pub fn parse_recipient(s: &str) -> Result<PublicKey> {
+    Ok(PublicKey::from(bech32_decode(s, RECIPIENT_HRP)?))
+}
+
+fn encode_recipient(pk: &PublicKey) -> String {
+    let hrp = Hrp::parse_unchecked(RECIPIENT_HRP);
+    bech32::encode_lower::<Bech32>(hrp, pk.as_bytes()).expect("32 bytes always fit in bech32")
+}
+
+fn bech32_decode(s: &str, expected_hrp: &str) -> Result<[u8; 32]> {
+    let (hrp, data) =
+        bech32::decode(s.trim()).map_err(|e| anyhow::anyhow!("Invalid bech32: {}", e))?;
+    if hrp.to_lowercase() != expected_hrp {
+        anyhow::bail!("Expected {}1... key, got {}1...", expected_hrp, hrp);
+    }
+    data.try_into()
+        .map_err(|_| anyhow::anyhow!("Key must be 32 bytes"))
+}
+
+fn random_file_key() -> FileKey {
+    let mut key = Zeroizing::new([0u8; FILE_KEY_SIZE]);
+    OsRng.fill_bytes(key.as_mut());
+    key
+}
+
+// -> X25519 <ephemeral share>，wrap key = HKDF(shared secret, salt = share || recipient)
+fn wrap_x25519(file_key: &FileKey, recipient: &PublicKey) -> Result<Stanza> {
+    let ephemeral = StaticSecret::random_from_rng(OsRng);
+    let share = PublicKey::from(&ephemeral);
+    let shared = ephemeral.diffie_hellman(recipient);
+    if !shared.was_contributory() {
+        anyhow::bail!("Invalid X25519 recipient");
+    }
+
+    let wrap_key = x25519_wrap_key(shared.as_bytes(), &share, recipient)?;
+    Ok(Stanza {
+        tag: "X25519".into(),
+        args: vec![STANDARD_NO_PAD.encode(share.as_bytes())],
+        body: aead_seal(&wrap_key, file_key.as_ref())?,
+    })
+}
+
+fn unwrap_x25519(stanza: &Stanza, identity: &AgeIdentity) -> Result<Option<FileKey>> {
+    let [share] = stanza.args.as_slice() else {
+        anyhow::bail!("Malformed X25519 stanza");
+    };
+    let share: [u8; 32] = STANDARD_NO_PAD
+        .decode(share)?
+        .try_into()
+        .map_err(|_| anyhow::anyhow!("Malformed X25519 stanza"))?;
+    if stanza.body.len() != FILE_KEY_SIZE + TAG_SIZE {
+        anyhow::bail!("Malformed X25519 stanza body");
+    }
+
+    let share = PublicKey::from(share);
+    let shared = identity.secret.diffie_hellman(&share);
+    if !shared.was_contributory() {
+        anyhow::bail!("Invalid X25519 stanza share");
+    }
+    let recipient = PublicKey::from(&identity.secret);
+    let wrap_key = x25519_wrap_key(shared.as_bytes(), &share, &recipient)?;
+    // 解不开说明不是发给这个 identity 的，继续尝试下一个
+    Ok(aead_open(&wrap_key, &stanza.body)
+        .and_then(to_file_key)
+        .ok())
+}
+
+fn x25519_wrap_key(
+    shared: &[u8],
+    share: &PublicKey,
+    recipient: &PublicKey,
+) -> Result<Zeroizing<[u8; 32]>> {
+    let mut salt = Vec::with_capacity(64);
+    salt.extend_from_slice(share.as_bytes());
+    salt.extend_from_slice(recipient.as_bytes());
+    hkdf(&salt, shared, X25519_LABEL)
+}
+
+// -> scrypt <salt> <log2 N>，scrypt 的 salt 为 label || salt
+fn wrap_scrypt(file_key: &FileKey, passphrase: &[u8], log_n: u8) -> Result<Stanza> {
+    let mut salt = [0u8; 16];
+    OsRng.fill_bytes(&mut salt);
+    let key = scrypt_key(passphrase, &salt, log_n)?;
+
+    Ok(Stanza {
+        tag: "scrypt".into(),
+        args: vec![STANDARD_NO_PAD.encode(salt), log_n.to_string()],
+        body: aead_seal(&key, file_key.as_ref())?,
+    })
+}
+
+fn unwrap_scrypt(stanza: &Stanza, passphrase: &[u8]) -> Result<FileKey> {
+    let [salt, log_n] = stanza.args.as_slice() else {
+        anyhow::bail!("Malformed scrypt stanza");
+    };
+    let salt = STANDARD_NO_PAD.decode(salt)?;
+    if salt.len() != 16 || stanza.body.len() != FILE_KEY_SIZE + TAG_SIZE {
+        anyhow::bail!("Malformed scrypt stanza");
+    }
+    // 不接受前导 0 等非规范的写法
+    let log_n: u8 = log_n
+        .parse()
+        .ok()
+        .filter(|n: &u8| n.to_string() == *log_n && *n > 0)
+        .ok_or_else(|| anyhow::anyhow!("Malformed scrypt work factor"))?;
+    if log_n > SCRYPT_MAX_LOG_N {
+        anyhow::bail!("scrypt work factor {} is too large", log_n);
+    }
+
+    let key = scrypt_key(passphrase, &salt, log_n)?;
+    aead_open(&key, &stanza.body)
+        .and_then(to_file_key)
+        .map_err(|_| anyhow::anyhow!("Incorrect passphrase"))
+}
+
+fn scrypt_key(passphrase: &[u8], salt: &[u8], log_n: u8) -> Result<Zeroizing<[u8; 32]>> {
+    let mut full_salt = SCRYPT_LABEL.to_vec();
+    full_salt.extend_from_slice(salt);
+    let params = scrypt::Params::new(log_n, 8, 1, 32)
+        .map_err(|e| anyhow::anyhow!("Invalid scrypt parameters: {}", e))?;
+    let mut key = Zeroizing::new([0u8; 32]);
+    scrypt::scrypt(passphrase, &full_salt, &params, key.as_mut())
+        .map_err(|e| anyhow::anyhow!("scrypt failed: {}", e))?;
+    Ok(key)
+}
+
+fn encrypt_with_stanzas(
+    reader: &mut dyn Read,
+    writer: &mut dyn Write,
+    file_key: &FileKey,
+    stanzas: &[Stanza],
+) -> Result<()> {
+    let header = encode_header(stanzas);
+    let mut mac = header_hmac(file_key)?;
+    mac.update(&header);
+    writer.write_all(&header)?;
+    writeln!(
+        writer,
+        " {}",
+        STANDARD_NO_PAD.encode(mac.finalize().into_bytes())
+    )?;
+
+    let mut nonce = [0u8; 16];
+    OsRng.fill_bytes(&mut nonce);
+    writer.write_all(&nonce)?;
+    let payload_key = hkdf(&nonce, file_key.as_ref(), b"payload")?;
+
+    let mut current = vec![0u8; CHUNK_SIZE];
+    let mut next = vec![0u8; CHUNK_SIZE];
+    let mut n = read_full(reader, &mut current)?;
+    let mut counter = 0u64;
+    // 只有输入为空时最后一块才是空的，正好整块时最后一块就是满的
+    loop {
+        let m = if n == CHUNK_SIZE {
+            read_full(reader, &mut next)?
+        } else {
+            0
+        };
+        let last = m == 0;
+        let cipher = ChaCha20Poly1305::new(payload_key.as_ref().into());
+        let chunk = cipher
+            .encrypt(&chunk_nonce(counter, last).into(), &current[..n])
+            .map_err(|_| anyhow::anyhow!("Encryption failed"))?;
+        writer.write_all(&chunk)?;
+        if last {
+            return Ok(());
+        }
+        std::mem::swap(&mut current, &mut next);
+        n = m;
+        counter += 1;
+    }
+}
+
+fn decrypt_stream(
+    reader: &mut dyn BufRead,
+    writer: &mut dyn Write,
+    identities: &[AgeIdentity],
+    passphrase: impl Fn() -> Result<Zeroizing<Vec<u8>>>,
+) -> Result<()> {
+    let (stanzas, header, mac) = parse_header(reader)?;
+    let file_key = unwrap_file_key(&stanzas, identities, passphrase)?;
+
+    let mut verifier = header_hmac(&file_key)?;
+    verifier.update(&header);
+    verifier
+        .verify_slice(&mac)
+        .map_err(|_| anyhow::anyhow!("Header MAC mismatch, the file has been modified"))?;
+
+    let mut nonce = [0u8; 16];
+    reader
+        .read_exact(&mut nonce)
+        .map_err(|_| anyhow::anyhow!("age payload is truncated"))?;
+    let payload_key = hkdf(&nonce, file_key.as_ref(), b"payload")?;
+
+    let chunk_size = CHUNK_SIZE + TAG_SIZE;
+    let mut current = vec![0u8; chunk_size];
+    let mut next = vec![0u8; chunk_size];
+    let mut n = read_full(reader, &mut current)?;
+    let mut counter = 0u64;
+    loop {
+        let m = if n == chunk_size {
+            read_full(reader, &mut next)?
+        } else {
+            0
+        };
+        let last = m == 0;
+        let cipher = ChaCha20Poly1305::new(payload_key.as_ref().into());
+        let chunk = cipher
+            .decrypt(&chunk_nonce(counter, last).into(), &current[..n])
+            .map_err(|_| anyhow::anyhow!("Payload is corrupted or truncated"))?;
+        if last && chunk.is_empty() && counter > 0 {
+            anyhow::bail!("Payload has a trailing empty chunk");
+        }
+        writer.write_all(&chunk)?;
+        if last {
+            return Ok(());
+        }
+        std::mem::swap(&mut current, &mut next);
+        n = m;
+        counter += 1;
+    }
+}
+
+fn unwrap_file_key(
+    stanzas: &[Stanza],
+    identities: &[AgeIdentity],
+    passphrase: impl Fn() -> Result<Zeroizing<Vec<u8>>>,
+) -> Result<FileKey> {
+    if let Some(stanza) = stanzas.iter().find(|s| s.tag == "scrypt") {
+        // scrypt stanza 必须是唯一的 stanza，否则可以被用来绕过口令
+        if stanzas.len() != 1 {
+            anyhow::bail!("scrypt stanza must be the only stanza");
+        }
+        return unwrap_scrypt(stanza, &passphrase()?);
+    }
+
+    for stanza in stanzas.iter().filter(|s| s.tag == "X25519") {
+        for identity in identities {
+            if let Some(key) = unwrap_x25519(stanza, identity)? {
+                return Ok(key);
+            }
+        }
+    }
+
+    anyhow::bail!("No identity matched any of the file's recipients")
+}
+
+fn encode_header(stanzas: &[Stanza]) -> Vec<u8> {
+    let mut header = format!("{}\n", INTRO);
+    for stanza in stanzas {
+        header.push_str("-> ");
+        header.push_str(&stanza.tag);
+        for arg in &stanza.args {
+            header.push(' ');
+            header.push_str(arg);
+        }
+        header.push('\n');
+        // 最后一行必须少于 64 个字符，正好整行时补一个空行
+        let body = STANDARD_NO_PAD.encode(&stanza.body);
+        for line in body.as_bytes().chunks(COLUMNS) {
+            header.push_str(std::str::from_utf8(line).expect("base64 is ascii"));
+            header.push('\n');
+        }
+        if body.len() % COLUMNS == 0 {
+            header.push('\n');
+        }
+    }
+    header.push_str("---");
+    header.into_bytes()
+}
+
+/// 返回 stanza、参与 MAC 计算的 header 原文（到 --- 为止）和 MAC
+fn parse_header(reader: &mut dyn BufRead) -> Result<(Vec<Stanza>, Vec<u8>, Vec<u8>)> {
+    let mut header = Vec::new();
+    let intro = read_header_line(reader, &mut header)?;
+    if intro != INTRO {
+        anyhow::bail!("Input is not an age v1 file");
+    }
+
+    let mut stanzas = Vec::new();
+    loop {
+        let start = header.len();
+        let line = read_header_line(reader, &mut header)?;
+        if let Some(mac) = line.strip_prefix("--- ") {
+            // MAC 不参与自身的计算，header 只保留到 ---
+            header.truncate(start + 3);
+            let mac = STANDARD_NO_PAD.decode(mac)?;
+            if stanzas.is_empty() {
+                anyhow::bail!("age header has no recipients");
+            }
+            return Ok((stanzas, header, mac));
+        }
+
+        let mut parts = line
+            .strip_prefix("-> ")
+            .ok_or_else(|| anyhow::anyhow!("Malformed age header line: {:?}", line))?
+            .split(' ');
+        let tag = parts.next().unwrap_or_default().to_string();
+        let args: Vec<String> = parts.map(String::from).collect();
+        if tag.is_empty() || args.iter().any(|a| a.is_empty()) {
+            anyhow::bail!("Malformed age stanza: {:?}", line);
+        }
+
+        let mut body = String::new();
+        loop {
+            let line = read_header_line(reader, &mut header)?;
+            if line.len() > COLUMNS {
+                anyhow::bail!("age stanza body line is too long");
+            }
+            body.push_str(&line);
+            if line.len() < COLUMNS {
+                break;
+            }
+        }
+        stanzas.push(Stanza {
+            tag,
+            args,
+            body: STANDARD_NO_PAD.decode(&body)?,
+        });
+    }
+}
+
+// header 中只允许可打印的 ASCII，行尾必须是 \n
+fn read_header_line(reader: &mut dyn BufRead, header: &mut Vec<u8>) -> Result<String> {
+    let mut line = Vec::new();
+    // 单行长度有限，防止读入没有换行的二进制数据
+    Read::take(&mut *reader, 1024).read_until(b'\n', &mut line)?;
+    if line.pop() != Some(b'\n') {
+        anyhow::bail!("age header is truncated or malformed");
+    }
+    if !line.iter().all(|b| (0x20..0x7f).contains(b)) {
+        anyhow::bail!("age header contains invalid characters");
+    }
+    header.extend_from_slice(&line);
+    header.push(b'\n');
+    Ok(String::from_utf8(line)?)
+}
+
+fn header_hmac(file_key: &FileKey) -> Result<Hmac<Sha256>> {
+    let key = hkdf(&[], file_key.as_ref(), b"header")?;
+    Ok(<Hmac<Sha256> as Mac>::new_from_slice(&key[..])?)
+}
+
+fn hkdf(salt: &[u8], ikm: &[u8], info: &[u8]) -> Result<Zeroizing<[u8; 32]>> {
+    let mut key = Zeroizing::new([0u8; 32]);
+    Hkdf::<Sha256>::new(Some(salt), ikm)
+        .expand(info, key.as_mut())
+        .map_err(|e| anyhow::anyhow!("HKDF failed: {}", e))?;
+    Ok(key)
+}
+
+// stanza 中的 file key 使用全 0 nonce，每个 wrap key 只使用一次
+fn aead_seal(key: &[u8; 32], data: &[u8]) -> Result<Vec<u8>> {
+    ChaCha20Poly1305::new(key.into())
+        .encrypt(&[0u8; 12].into(), data)
+        .map_err(|_| anyhow::anyhow!("Encryption failed"))
+}
+
+fn aead_open(key: &[u8; 32], data: &[u8]) -> Result<Vec<u8>> {
+    ChaCha20Poly1305::new(key.into())
+        .decrypt(&[0u8; 12].into(), data)
+        .map_err(|_| anyhow::anyhow!("Decryption failed"))
+}
+
+fn to_file_key(data: Vec<u8>) -> Result<FileKey> {
+    let data = Zeroizing::new(data);
+    let key: [u8; FILE_KEY_SIZE] = data[..]
+        .try_into()
+        .map_err(|_| anyhow::anyhow!("Invalid file key length"))?;
+    Ok(Zeroizing::new(key))
+}
+
+// 11 字节的大端计数器 + 1 字节的最后一块标记
+fn chunk_nonce(counter: u64, last: bool) -> [u8; 12] {
+    let mut nonce = [0u8; 12];
+    nonce[3..11].copy_from_slice(&counter.to_be_bytes());
+    nonce[11] = last as u8;
+    nonce
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+
+    fn encrypt(data: &[u8], stanzas: &[Stanza], file_key: &FileKey) -> Result<Vec<u8>> {
+        let mut out = Vec::new();
+        encrypt_with_stanzas(&mut &data[..], &mut out, file_key, stanzas)?;
+        Ok(out)
+    }
+
+    fn decrypt(data: &[u8], identities: &[AgeIdentity], passphrase: &str) -> Result<Vec<u8>> {
+        let mut out = Vec::new();
+        decrypt_stream(&mut &data[..], &mut out, identities, || {
+            Ok(Zeroizing::new(passphrase.as_bytes().to_vec()))
+        })?;
+        Ok(out)
+    }
+
+    #[test]
+    fn test_identity_encoding() -> Result<()> {
+        let identity = AgeIdentity::generate();
+        let secret = identity.to_secret_string();
+        assert!(secret.starts_with("AGE-SECRET-KEY-1"));
+        assert_eq!(secret.len(), 74);
+        assert!(identity.recipient().starts_with("age1"));
+        assert_eq!(identity.recipient().len(), 62);
+
+        let parsed = AgeIdentity::parse(&secret)?;
+        assert_eq!(parsed.recipient(), identity.recipient());
+        // 公钥和私钥的 hrp 不能混用
+        assert!(AgeIdentity::parse(&identity.recipient()).is_err());
+        assert!(parse_recipient(&secret).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_x25519_roundtrip() -> Result<()> {
+        let alice = AgeIdentity::generate();
+        let bob = AgeIdentity::generate();
+        let file_key = random_file_key();
+        let stanzas = vec![
+            wrap_x25519(&file_key, &parse_recipient(&alice.recipient())?)?,
+            wrap_x25519(&file_key, &parse_recipient(&bob.recipient())?)?,
+        ];
+
+        let encrypted = encrypt(b"hello world", &stanzas, &file_key)?;
+        assert!(encrypted.starts_with(b"age-encryption.org/v1\n-> X25519 "));
+        assert_eq!(decrypt(&encrypted, &[alice], "")?, b"hello world");
+        // 每个 identity 都会尝试，第一个不匹配也没关系
+        assert_eq!(
+            decrypt(&encrypted, &[AgeIdentity::generate(), bob], "")?,
+            b"hello world"
+        );
+        assert!(decrypt(&encrypted, &[AgeIdentity::generate()], "").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_payload_sizes() -> Result<()> {
+        let identity = AgeIdentity::generate();
+        let file_key = random_file_key();
+        let stanzas = vec![wrap_x25519(
+            &file_key,
+            &parse_recipient(&identity.recipient())?,
+        )?];
+
+        // 覆盖空输入、正好一块和多块的情况，正好整块时没有额外的空块
+        for len in [0, 1, CHUNK_SIZE, CHUNK_SIZE + 1, 2 * CHUNK_SIZE] {
+            let data: Vec<u8> = (0..len).map(|i| i as u8).collect();
+            let encrypted = encrypt(&data, &stanzas, &file_key)?;
+            let chunks = len.div_ceil(CHUNK_SIZE).max(1);
+            let header_len = encode_header(&stanzas).len() + 1 + 43 + 1;
+            assert_eq!(encrypted.len(), header_len + 16 + len + chunks * TAG_SIZE);
+            assert_eq!(
+                decrypt(&encrypted, std::slice::from_ref(&identity), "")?,
+                data
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_scrypt_roundtrip() -> Result<()> {
+        let file_key = random_file_key();
+        // 测试中使用很小的 work factor
+        let stanzas = vec![wrap_scrypt(&file_key, b"secret", 4)?];
+        let encrypted = encrypt(b"hello world", &stanzas, &file_key)?;
+        assert_eq!(decrypt(&encrypted, &[], "secret")?, b"hello world");
+        assert!(decrypt(&encrypted, &[], "wrong").is_err());
+
+        // scrypt stanza 和其他 stanza 混用时拒绝
+        let identity = AgeIdentity::generate();
+        let mixed = vec![
+            wrap_scrypt(&file_key, b"secret", 4)?,
+            wrap_x25519(&file_key, &parse_recipient(&identity.recipient())?)?,
+        ];
+        let encrypted = encrypt(b"hello world", &mixed, &file_key)?;
+        assert!(decrypt(&encrypted, &[identity], "secret").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_tampering_is_detected() -> Result<()> {
+        let identity = AgeIdentity::generate();
+        let file_key = random_file_key();
+        let stanzas = vec![wrap_x25519(
+            &file_key,
+            &parse_recipient(&identity.recipient())?,
+        )?];
+        let encrypted = encrypt(&[7u8; 100], &stanzas, &file_key)?;
+        let secret = identity.to_secret_string();
+        let identity = || AgeIdentity::parse(&secret).unwrap();
+
+        // 修改 payload
+        let mut flipped = encrypted.clone();
+        *flipped.last_mut().unwrap() ^= 1;
+        assert!(decrypt(&flipped, &[identity()], "").is_err());
+
+        // 截断 payload
+        assert!(decrypt(&encrypted[..encrypted.len() - 1], &[identity()], "").is_err());
+
+        // 在 header 中加一个 stanza，MAC 不匹配
+        let pos = encrypted.windows(4).position(|w| w == b"\n---").unwrap() + 1;
+        let mut injected = encrypted[..pos].to_vec();
+        injected.extend_from_slice(b"-> grease\n\n");
+        injected.extend_from_slice(&encrypted[pos..]);
+        assert!(decrypt(&injected, &[identity()], "").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_failed_decrypt_removes_output() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let identity = AgeIdentity::generate();
+        let identity_file = dir.path().join("key.txt");
+        fs::write(&identity_file, identity.to_secret_string().as_bytes())?;
+        let identity_file = identity_file.to_str().unwrap().to_string();
+
+        let file_key = random_file_key();
+        let stanzas = vec![wrap_x25519(
+            &file_key,
+            &parse_recipient(&identity.recipient())?,
+        )?];
+        // 超过两块，截断后第一块已经解密并写入
+        let encrypted = encrypt(&vec![7u8; 3 * CHUNK_SIZE], &stanzas, &file_key)?;
+        let truncated = dir.path().join("truncated.age");
+        fs::write(&truncated, &encrypted[..encrypted.len() - CHUNK_SIZE])?;
+
+        let output = dir.path().join("out.bin");
+        let output = output.to_str().unwrap();
+        assert!(
+            process_age_decrypt(truncated.to_str().unwrap(), output, &[identity_file]).is_err()
+        );
+        assert!(!Path::new(output).exists());
+        Ok(())
+    }
+
+    #[test]
+    fn test_header_encoding() -> Result<()> {
+        let stanzas = vec![
+            Stanza {
+                tag: "X25519".into(),
+                args: vec!["abc".into()],
+                body: vec![1u8; 32],
+            },
+            // body 正好 64 个字符时要补一个空行
+            Stanza {
+                tag: "test".into(),
+                args: vec![],
+                body: vec![2u8; 48],
+            },
+        ];
+        let mut header = encode_header(&stanzas);
+        let text = String::from_utf8(header.clone())?;
+        assert_eq!(
+            text,
+            format!(
+                "age-encryption.org/v1\n-> X25519 abc\n{}\n-> test\n{}\n\n---",
+                STANDARD_NO_PAD.encode([1u8; 32]),
+                STANDARD_NO_PAD.encode([2u8; 48])
+            )
+        );
+
+        header.extend_from_slice(b" AAAA\n");
+        let (parsed, raw, mac) = parse_header(&mut &header[..])?;
+        assert_eq!(parsed, stanzas);
+        assert_eq!(raw, text.as_bytes());
+        assert_eq!(mac, STANDARD_NO_PAD.decode("AAAA")?);
+        Ok(())
+    }
+}
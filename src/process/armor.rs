@@ -0,0 +1,262 @@
+use std::io::{self, BufRead, Read, Write};
+use std::path::Path;
+
+use anyhow::Result;
+
+use crate::{encode_stream, get_reader, get_writer, EncodeStyle, Encoding};
+
+// PEM 规定每行 64 个字符
+const PEM_WIDTH: usize = 64;
+// 嗅探 MIME 类型时读取的文件头长度
+const SNIFF_LEN: u64 = 32;
+
+/// 输出 data:<mime>;base64,... 形式的 data URI，MIME 类型先看文件头的 magic bytes，再看扩展名
+pub fn process_encode_data_uri(input: &str, output: &str) -> Result<()> {
+    let mut reader = get_reader(input)?;
+    let mut header = Vec::new();
+    (&mut reader).take(SNIFF_LEN).read_to_end(&mut header)?;
+
+    let path = if input == "-" { None } else { Some(input) };
+    let mime = sniff_mime(&header, path);
+
+    let mut writer = get_writer(output)?;
+    write!(writer, "data:{};base64,", mime)?;
+    // 读出来的文件头要重新拼回到输入的前面
+    let mut reader = io::Cursor::new(header).chain(reader);
+    encode_stream(
+        &mut reader,
+        &mut writer,
+        Encoding::Base64,
+        &EncodeStyle::default(),
+    )?;
+    writer.flush()?;
+
+    Ok(())
+}
+
+/// 输出 -----BEGIN LABEL----- / -----END LABEL----- 包裹的 PEM，正文每行 64 个字符
+pub fn process_encode_pem(input: &str, output: &str, label: &str) -> Result<()> {
+    if label.is_empty()
+        || !label
+            .bytes()
+            .all(|b| b.is_ascii_uppercase() || b.is_ascii_digit() || b == b' ')
+    {
+        anyhow::bail!("PEM label must be uppercase letters, digits and spaces");
+    }
+
+    let mut reader = get_reader(input)?;
+    let mut writer = get_writer(output)?;
+    let style = EncodeStyle {
+        wrap: PEM_WIDTH,
+        ..Default::default()
+    };
+    writeln!(writer, "-----BEGIN {}-----", label)?;
+    encode_stream(&mut reader, &mut writer, Encoding::Base64, &style)?;
+    writeln!(writer, "-----END {}-----", label)?;
+    writer.flush()?;
+
+    Ok(())
+}
+
+/// 去掉 PEM 或 data URI 的外层包装，返回只包含 base64 正文的 reader
+/// 两种都不是时原样返回，只会消耗开头的空白字符
+pub fn strip_armor<'a>(reader: &'a mut dyn BufRead) -> Result<Box<dyn Read + 'a>> {
+    loop {
+        let buf = reader.fill_buf()?;
+        let n = buf.iter().take_while(|b| b.is_ascii_whitespace()).count();
+        if n == 0 {
+            break;
+        }
+        reader.consume(n);
+    }
+
+    let buf = reader.fill_buf()?;
+    if buf.starts_with(b"-----BEGIN ") {
+        let mut header = String::new();
+        reader.read_line(&mut header)?;
+        if !header.trim_end().ends_with("-----") {
+            anyhow::bail!("Invalid PEM header: {}", header.trim_end());
+        }
+        return Ok(Box::new(PemBody {
+            inner: reader,
+            line: Vec::new(),
+            pos: 0,
+            done: false,
+        }));
+    }
+
+    if buf.starts_with(b"data:") {
+        let mut header = Vec::new();
+        reader.read_until(b',', &mut header)?;
+        if header.last() != Some(&b',') {
+            anyhow::bail!("Invalid data URI, missing ','");
+        }
+        let header = String::from_utf8_lossy(&header);
+        if !header.trim_end_matches(',').ends_with(";base64") {
+            anyhow::bail!("Only base64 data URIs are supported");
+        }
+    }
+
+    Ok(Box::new(reader))
+}
+
+/// PEM 的正文，逐行读取，读到 -----END 开头的行时结束
+/// base64url 的字母表中有 -，不能遇到 - 就停止
+struct PemBody<R> {
+    inner: R,
+    line: Vec<u8>,
+    pos: usize,
+    done: bool,
+}
+
+impl<R: BufRead> Read for PemBody<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.pos == self.line.len() {
+            if self.done || buf.is_empty() {
+                return Ok(0);
+            }
+            self.line.clear();
+            self.pos = 0;
+            if self.inner.read_until(b'\n', &mut self.line)? == 0 {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "Missing PEM footer",
+                ));
+            }
+            if self.line.starts_with(b"-----END ") {
+                self.line.clear();
+                self.done = true;
+            }
+        }
+
+        let n = (self.line.len() - self.pos).min(buf.len());
+        buf[..n].copy_from_slice(&self.line[self.pos..self.pos + n]);
+        self.pos += n;
+
+        Ok(n)
+    }
+}
+
+/// 根据文件头的 magic bytes 判断 MIME 类型，判断不出来时再看扩展名
+pub fn sniff_mime(header: &[u8], path: Option<&str>) -> &'static str {
+    const MAGIC: &[(&[u8], &str)] = &[
+        (b"\x89PNG\r\n\x1a\n", "image/png"),
+        (b"\xff\xd8\xff", "image/jpeg"),
+        (b"GIF87a", "image/gif"),
+        (b"GIF89a", "image/gif"),
+        (b"%PDF-", "application/pdf"),
+        (b"PK\x03\x04", "application/zip"),
+        (b"\x1f\x8b", "application/gzip"),
+        (b"\x00asm", "application/wasm"),
+        (b"\x00\x00\x01\x00", "image/x-icon"),
+        (b"wOF2", "font/woff2"),
+        (b"BM", "image/bmp"),
+    ];
+
+    for (magic, mime) in MAGIC {
+        if header.starts_with(magic) {
+            return mime;
+        }
+    }
+    // RIFF 容器，第 8 ~ 12 字节是具体格式
+    if header.starts_with(b"RIFF") && header.get(8..12) == Some(b"WEBP") {
+        return "image/webp";
+    }
+
+    let ext = path
+        .and_then(|p| Path::new(p).extension())
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_lowercase());
+    match ext.as_deref() {
+        Some("png") => "image/png",
+        Some("jpg" | "jpeg") => "image/jpeg",
+        Some("gif") => "image/gif",
+        Some("webp") => "image/webp",
+        Some("svg") => "image/svg+xml",
+        Some("ico") => "image/x-icon",
+        Some("pdf") => "application/pdf",
+        Some("json") => "application/json",
+        Some("txt") => "text/plain",
+        Some("html" | "htm") => "text/html",
+        Some("css") => "text/css",
+        Some("js") => "text/javascript",
+        Some("csv") => "text/csv",
+        Some("xml") => "application/xml",
+        _ => "application/octet-stream",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{decode_stream, DecodeStyle};
+
+    fn decode_armored(data: &str) -> Result<Vec<u8>> {
+        decode_armored_with(data, Encoding::Base64)
+    }
+
+    fn decode_armored_with(data: &str, encoding: Encoding) -> Result<Vec<u8>> {
+        let mut decoded = Vec::new();
+        let style = DecodeStyle::default();
+        decode_stream(&mut data.as_bytes(), &mut decoded, encoding, &style)?;
+        Ok(decoded)
+    }
+
+    #[test]
+    fn test_sniff_mime() {
+        assert_eq!(sniff_mime(b"\x89PNG\r\n\x1a\n0000", None), "image/png");
+        assert_eq!(sniff_mime(b"RIFF\0\0\0\0WEBPVP8 ", None), "image/webp");
+        assert_eq!(sniff_mime(b"<svg>", Some("logo.SVG")), "image/svg+xml");
+        assert_eq!(
+            sniff_mime(b"hello", Some("fixtures/hello.txt")),
+            "text/plain"
+        );
+        assert_eq!(sniff_mime(b"hello", None), "application/octet-stream");
+    }
+
+    #[test]
+    fn test_data_uri_roundtrip() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let output = dir.path().join("data-uri.txt");
+        process_encode_data_uri("fixtures/hello.txt", output.to_str().unwrap())?;
+        let encoded = std::fs::read_to_string(&output)?;
+        assert!(encoded.starts_with("data:text/plain;base64,"));
+
+        assert_eq!(
+            decode_armored(&encoded)?,
+            std::fs::read("fixtures/hello.txt")?
+        );
+        assert!(decode_armored("data:text/plain,hello").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_pem_roundtrip() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let output = dir.path().join("pem.txt");
+        process_encode_pem("fixtures/index.html", output.to_str().unwrap(), "TEST DATA")?;
+        let encoded = std::fs::read_to_string(&output)?;
+        let lines: Vec<&str> = encoded.lines().collect();
+        assert_eq!(lines[0], "-----BEGIN TEST DATA-----");
+        assert_eq!(lines[lines.len() - 1], "-----END TEST DATA-----");
+        assert!(lines[1..lines.len() - 1]
+            .iter()
+            .all(|l| l.len() <= PEM_WIDTH));
+
+        assert_eq!(
+            decode_armored(&encoded)?,
+            std::fs::read("fixtures/index.html")?
+        );
+        assert!(decode_armored("-----BEGIN X-----\nZm9v\n").is_err());
+        // base64url 的正文可以以 - 开头，只有 -----END 行才是结尾
+        assert_eq!(
+            decode_armored_with(
+                "-----BEGIN X-----\n-_8-\n-----END X-----\n",
+                Encoding::Base64Url
+            )?,
+            [0xfb, 0xff, 0x3e]
+        );
+        assert!(process_encode_pem("fixtures/hello.txt", "-", "bad-label").is_err());
+        Ok(())
+    }
+}
@@ -1,41 +1,491 @@
-use std::io::Read;
+use std::io::{self, BufReader, Read, Write};
 
 use anyhow::Result;
-use base64::engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD};
+use base64::alphabet;
+use base64::engine::general_purpose::{
+    GeneralPurpose, STANDARD, STANDARD_NO_PAD, URL_SAFE, URL_SAFE_NO_PAD,
+};
+use base64::engine::{DecodePaddingMode, GeneralPurposeConfig};
+use base64::read::DecoderReader;
+use base64::write::EncoderWriter;
 use base64::Engine;
+use data_encoding::{
+    BASE32, BASE32HEX, BASE32HEX_NOPAD, BASE32_NOPAD, HEXLOWER, HEXLOWER_PERMISSIVE,
+};
 
-use crate::{get_reader, Base64Format};
+use crate::{
+    get_reader, get_writer, strip_armor, DecodeStyle, EncodeStyle, Encoding, HexdumpStyle,
+    HexdumpWriter,
+};
 
-pub fn process_encode(input: &str, format: Base64Format) -> Result<String> {
-    let mut reader = get_reader(input)?;
-    let mut buf = Vec::new();
-    reader.read_to_end(&mut buf)?;
+const Z85: &[u8] =
+    b"0123456789abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ.-:+=^!/*?&<>()[]{}@%$#";
 
-    let encode = match format {
-        Base64Format::Standard => STANDARD.encode(buf),
-        Base64Format::UrlSafe => URL_SAFE_NO_PAD.encode(buf),
-    };
+pub fn process_encode(
+    input: &str,
+    output: &str,
+    encoding: Encoding,
+    style: &EncodeStyle,
+) -> Result<()> {
+    let mut reader = get_reader(input)?;
+    let mut writer = get_writer(output)?;
+    encode_stream(&mut reader, &mut writer, encoding, style)?;
+    writer.flush()?;
 
-    Ok(encode)
+    Ok(())
 }
 
-pub fn process_decode(input: &str, format: Base64Format) -> Result<Vec<u8>> {
+pub fn process_decode(
+    input: &str,
+    output: &str,
+    encoding: Encoding,
+    style: &DecodeStyle,
+) -> Result<()> {
     let mut reader = get_reader(input)?;
+    let mut writer = get_writer(output)?;
+    if style.hexdump {
+        let mut dump = HexdumpWriter::new(&mut writer, HexdumpStyle::default());
+        decode_stream(&mut reader, &mut dump, encoding, style)?;
+        dump.finish()?;
+    } else {
+        decode_stream(&mut reader, &mut writer, encoding, style)?;
+    }
+    writer.flush()?;
+
+    Ok(())
+}
+
+/// --auto 识别出的编码，alternatives 是同样能解码、但结果不同的其他编码
+#[derive(Debug)]
+pub struct AutoDecoded {
+    pub encoding: Encoding,
+    pub alternatives: Vec<Encoding>,
+}
+
+// 按字母表从小到大排列，能被小字母表解析的数据更可能就是这种编码
+const AUTO_CANDIDATES: [Encoding; 5] = [
+    Encoding::Hex,
+    Encoding::Base32,
+    Encoding::Base58,
+    Encoding::Base64,
+    Encoding::Base64Url,
+];
+
+/// 自动识别编码并解码，多种编码都能解析时选择字母表最小的一种
+pub fn process_decode_auto(input: &str, output: &str, hexdump: bool) -> Result<AutoDecoded> {
+    let mut reader = BufReader::new(get_reader(input)?);
     let mut buf = String::new();
-    reader.read_to_string(&mut buf)?;
+    strip_armor(&mut reader)?.read_to_string(&mut buf)?;
+
+    let mut candidates = detect_encoding(&buf).into_iter();
+    let (encoding, data) = candidates
+        .next()
+        .ok_or_else(|| anyhow::anyhow!("Input is not valid base64, base58, base32 or hex"))?;
+    // 解码结果相同的编码只保留第一个，例如没有 + / - _ 时 base64 和 base64url 是一样的
+    let mut seen = vec![data.clone()];
+    let mut alternatives = Vec::new();
+    for (encoding, other) in candidates {
+        if !seen.contains(&other) {
+            alternatives.push(encoding);
+            seen.push(other);
+        }
+    }
+
+    let mut writer = get_writer(output)?;
+    if hexdump {
+        let mut dump = HexdumpWriter::new(&mut writer, HexdumpStyle::default());
+        dump.write_all(&data)?;
+        dump.finish()?;
+    } else {
+        writer.write_all(&data)?;
+    }
+    writer.flush()?;
+
+    Ok(AutoDecoded {
+        encoding,
+        alternatives,
+    })
+}
+
+/// 返回所有能成功解码的编码及解码结果，顺序和 AUTO_CANDIDATES 一致
+/// base64 使用宽松模式，带不带 padding 都可以
+pub fn detect_encoding(data: &str) -> Vec<(Encoding, Vec<u8>)> {
+    let data: String = data.chars().filter(|c| !c.is_whitespace()).collect();
+    if data.is_empty() {
+        return Vec::new();
+    }
+
+    AUTO_CANDIDATES
+        .into_iter()
+        .filter_map(|encoding| {
+            let decoded = match base64_engine(encoding, None, false) {
+                Some(engine) => engine.decode(&data).ok(),
+                None => decode(&data, encoding).ok(),
+            };
+            decoded.map(|decoded| (encoding, decoded))
+        })
+        .collect()
+}
+
+/// base64 边读边编码，内存占用和输入大小无关
+/// base58 是整体的进制转换，其他编码的数据量也不大，仍然一次性读入
+pub fn encode_stream(
+    reader: &mut dyn Read,
+    writer: &mut dyn Write,
+    encoding: Encoding,
+    style: &EncodeStyle,
+) -> Result<()> {
+    if style.multibase {
+        // multibase 一般用于 CID 等标识符，数据量很小，不换行
+        let mut buf = Vec::new();
+        reader.read_to_end(&mut buf)?;
+        writer.write_all(multibase_encode(&buf, encoding)?.as_bytes())?;
+        writer.write_all(b"\n")?;
+        return Ok(());
+    }
+
+    let mut wrapper = LineWrapper::new(&mut *writer, style.wrap);
+    match base64_engine(encoding, style.padding(), false) {
+        Some(engine) => {
+            let mut encoder = EncoderWriter::new(&mut wrapper, &engine);
+            io::copy(reader, &mut encoder)?;
+            encoder.finish()?;
+        }
+        None => {
+            let mut buf = Vec::new();
+            reader.read_to_end(&mut buf)?;
+            let encoded = match (encoding, style.padding()) {
+                (Encoding::Base32, Some(false)) => BASE32_NOPAD.encode(&buf),
+                (Encoding::Base32Hex, Some(false)) => BASE32HEX_NOPAD.encode(&buf),
+                (Encoding::Base32 | Encoding::Base32Hex, _) | (_, None) => encode(&buf, encoding)?,
+                _ => anyhow::bail!("--pad / --no-pad only apply to base64 and base32"),
+            };
+            wrapper.write_all(encoded.as_bytes())?;
+        }
+    }
+    writer.write_all(b"\n")?;
+
+    Ok(())
+}
+
+/// 输出原始字节，解码后的数据可能是图片、压缩包等二进制数据
+pub fn decode_stream(
+    reader: &mut dyn Read,
+    writer: &mut dyn Write,
+    encoding: Encoding,
+    style: &DecodeStyle,
+) -> Result<()> {
+    if style.multibase {
+        let mut buf = String::new();
+        reader.read_to_string(&mut buf)?;
+        let (_, data) = multibase_decode(buf.trim())?;
+        writer.write_all(&data)?;
+        return Ok(());
+    }
+
+    match base64_engine(encoding, style.padding(), style.strict) {
+        Some(engine) => {
+            // PEM 和 data URI 的外层包装自动去掉
+            let mut reader = BufReader::new(reader);
+            let body = strip_armor(&mut reader)?;
+            // 忽略换行等空白字符，可以直接解码 --wrap 的输出
+            let mut decoder = DecoderReader::new(SkipWhitespace(body), &engine);
+            io::copy(&mut decoder, writer)?;
+        }
+        None => {
+            let mut buf = String::new();
+            reader.read_to_string(&mut buf)?;
+            // 和 base64 一样忽略所有空白字符，可以解码 --wrap 的输出
+            buf.retain(|c| !c.is_ascii_whitespace());
+            let decoded = match (encoding, style.padding()) {
+                (Encoding::Base32, Some(false)) => {
+                    BASE32_NOPAD.decode(buf.to_uppercase().as_bytes())?
+                }
+                (Encoding::Base32Hex, Some(false)) => {
+                    BASE32HEX_NOPAD.decode(buf.to_uppercase().as_bytes())?
+                }
+                (Encoding::Base32 | Encoding::Base32Hex, _) | (_, None) => decode(&buf, encoding)?,
+                _ => anyhow::bail!("--pad / --no-pad only apply to base64 and base32"),
+            };
+            writer.write_all(&decoded)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// multibase 编码：一个字符的前缀 + 对应的编码，前缀表见
+/// https://github.com/multiformats/multibase/blob/master/multibase.csv
+/// multibase 中 base32 / hex 使用小写，base32 和 base64 都不带 padding
+pub fn multibase_encode(data: &[u8], encoding: Encoding) -> Result<String> {
+    let (prefix, encoded) = match encoding {
+        Encoding::Base58 => ('z', bs58::encode(data).into_string()),
+        Encoding::Base32 => ('b', BASE32_NOPAD.encode(data).to_lowercase()),
+        Encoding::Base32Hex => ('v', BASE32HEX_NOPAD.encode(data).to_lowercase()),
+        Encoding::Base64 => ('m', STANDARD_NO_PAD.encode(data)),
+        Encoding::Base64Url => ('u', URL_SAFE_NO_PAD.encode(data)),
+        Encoding::Hex => ('f', HEXLOWER.encode(data)),
+        _ => anyhow::bail!("{} has no multibase prefix", encoding),
+    };
+
+    Ok(format!("{}{}", prefix, encoded))
+}
+
+/// 根据前缀解码 multibase 字符串，返回对应的编码和解码后的数据
+pub fn multibase_decode(data: &str) -> Result<(Encoding, Vec<u8>)> {
+    let mut chars = data.chars();
+    let prefix = chars
+        .next()
+        .ok_or_else(|| anyhow::anyhow!("Empty multibase string"))?;
+    let body = chars.as_str();
+
+    let ret = match prefix {
+        'z' => (Encoding::Base58, bs58::decode(body).into_vec()?),
+        'b' | 'B' => (
+            Encoding::Base32,
+            BASE32_NOPAD.decode(body.to_uppercase().as_bytes())?,
+        ),
+        'c' | 'C' => (
+            Encoding::Base32,
+            BASE32.decode(body.to_uppercase().as_bytes())?,
+        ),
+        'v' | 'V' => (
+            Encoding::Base32Hex,
+            BASE32HEX_NOPAD.decode(body.to_uppercase().as_bytes())?,
+        ),
+        't' | 'T' => (
+            Encoding::Base32Hex,
+            BASE32HEX.decode(body.to_uppercase().as_bytes())?,
+        ),
+        'f' | 'F' => (Encoding::Hex, HEXLOWER_PERMISSIVE.decode(body.as_bytes())?),
+        'm' => (Encoding::Base64, STANDARD_NO_PAD.decode(body)?),
+        'M' => (Encoding::Base64, STANDARD.decode(body)?),
+        'u' => (Encoding::Base64Url, URL_SAFE_NO_PAD.decode(body)?),
+        'U' => (Encoding::Base64Url, URL_SAFE.decode(body)?),
+        _ => anyhow::bail!("Unsupported multibase prefix: {}", prefix),
+    };
+
+    Ok(ret)
+}
+
+/// 根据 padding 和 strict 构造 base64 engine，非 base64 编码返回 None
+/// 宽松模式下 padding 可有可无，也允许最后一个字符中多余的 bit 不为 0
+fn base64_engine(encoding: Encoding, pad: Option<bool>, strict: bool) -> Option<GeneralPurpose> {
+    let (alphabet, default_pad) = match encoding {
+        Encoding::Base64 => (&alphabet::STANDARD, true),
+        Encoding::Base64Url => (&alphabet::URL_SAFE, false),
+        _ => return None,
+    };
+    let pad = pad.unwrap_or(default_pad);
+    let mode = match (strict, pad) {
+        (false, _) => DecodePaddingMode::Indifferent,
+        (true, true) => DecodePaddingMode::RequireCanonical,
+        (true, false) => DecodePaddingMode::RequireNone,
+    };
+    let config = GeneralPurposeConfig::new()
+        .with_encode_padding(pad)
+        .with_decode_padding_mode(mode)
+        .with_decode_allow_trailing_bits(!strict);
+
+    Some(GeneralPurpose::new(alphabet, config))
+}
+
+/// 每写入 width 个字节插入一个换行，width 为 0 时不换行
+struct LineWrapper<W> {
+    inner: W,
+    width: usize,
+    column: usize,
+}
+
+impl<W: Write> LineWrapper<W> {
+    fn new(inner: W, width: usize) -> Self {
+        Self {
+            inner,
+            width,
+            column: 0,
+        }
+    }
+}
+
+impl<W: Write> Write for LineWrapper<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.width == 0 {
+            return self.inner.write(buf);
+        }
+
+        let mut rest = buf;
+        while !rest.is_empty() {
+            // 行满了之后，在下一个字符之前换行，避免结尾多出一个空行
+            if self.column == self.width {
+                self.inner.write_all(b"\n")?;
+                self.column = 0;
+            }
+            let n = rest.len().min(self.width - self.column);
+            self.inner.write_all(&rest[..n])?;
+            self.column += n;
+            rest = &rest[n..];
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// 读取时跳过空白字符，DecoderReader 遇到换行会报错
+struct SkipWhitespace<R>(R);
+
+impl<R: Read> Read for SkipWhitespace<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            let n = self.0.read(buf)?;
+            if n == 0 {
+                return Ok(0);
+            }
+            let mut len = 0;
+            for i in 0..n {
+                if !buf[i].is_ascii_whitespace() {
+                    buf[len] = buf[i];
+                    len += 1;
+                }
+            }
+            // 读到的全是空白时继续读，返回 0 会被当作 EOF
+            if len > 0 {
+                return Ok(len);
+            }
+        }
+    }
+}
+
+pub fn encode(data: &[u8], encoding: Encoding) -> Result<String> {
+    let encode = match encoding {
+        Encoding::Base64 => STANDARD.encode(data),
+        Encoding::Base64Url => URL_SAFE_NO_PAD.encode(data),
+        Encoding::Base58 => bs58::encode(data).into_string(),
+        Encoding::Base58Check => bs58::encode(data).with_check().into_string(),
+        Encoding::Base32 => BASE32.encode(data),
+        Encoding::Base32Hex => BASE32HEX.encode(data),
+        Encoding::Hex => HEXLOWER.encode(data),
+        Encoding::Z85 => {
+            // Z85 规范只定义了 4 字节整数倍的输入，不像 Ascii85 那样处理不完整的分组
+            if !data.len().is_multiple_of(4) {
+                anyhow::bail!("Z85 input length must be a multiple of 4 bytes");
+            }
+            base85_encode(data, Z85, false)
+        }
+        Encoding::Ascii85 => base85_encode(data, &ASCII85, true),
+    };
 
-    // avoid accidental newlines
-    let buf = buf.trim();
+    Ok(encode)
+}
 
-    let decode = match format {
-        Base64Format::Standard => STANDARD.decode(buf)?,
-        Base64Format::UrlSafe => URL_SAFE_NO_PAD.decode(buf)?,
+pub fn decode(data: &str, encoding: Encoding) -> Result<Vec<u8>> {
+    let decode = match encoding {
+        Encoding::Base64 => STANDARD.decode(data)?,
+        Encoding::Base64Url => URL_SAFE_NO_PAD.decode(data)?,
+        Encoding::Base58 => bs58::decode(data).into_vec()?,
+        Encoding::Base58Check => bs58::decode(data).with_check(None).into_vec()?,
+        // base32 的字母表不区分大小写
+        Encoding::Base32 => BASE32.decode(data.to_uppercase().as_bytes())?,
+        Encoding::Base32Hex => BASE32HEX.decode(data.to_uppercase().as_bytes())?,
+        Encoding::Hex => HEXLOWER_PERMISSIVE.decode(data.as_bytes())?,
+        Encoding::Z85 => {
+            let len = data.bytes().filter(|c| !c.is_ascii_whitespace()).count();
+            if !len.is_multiple_of(5) {
+                anyhow::bail!("Z85 text length must be a multiple of 5 characters");
+            }
+            base85_decode(data, Z85, false)?
+        }
+        Encoding::Ascii85 => {
+            // 兼容 Adobe 风格的 <~ ~> 定界符
+            let data = data.strip_prefix("<~").unwrap_or(data);
+            let data = data.strip_suffix("~>").unwrap_or(data);
+            base85_decode(data, &ASCII85, true)?
+        }
     };
 
-    // todo Decode date might not be string (but for this example, we assume it is)
     Ok(decode)
 }
 
+// Ascii85 使用 ! 到 u 连续的 85 个字符
+const ASCII85: [u8; 85] = {
+    let mut alphabet = [0u8; 85];
+    let mut i = 0;
+    while i < 85 {
+        alphabet[i] = b'!' + i as u8;
+        i += 1;
+    }
+    alphabet
+};
+
+/// 每 4 个字节看作一个大端 u32，转换成 5 个 85 进制的字符
+/// 最后不足 4 个字节时补 0，只输出 n + 1 个字符，和 Ascii85 的处理方式一致
+/// zero_group 为 true 时，全 0 的完整分组输出为 z（Ascii85 的缩写）
+fn base85_encode(data: &[u8], alphabet: &[u8], zero_group: bool) -> String {
+    let mut ret = String::with_capacity(data.len().div_ceil(4) * 5);
+    for chunk in data.chunks(4) {
+        let mut block = [0u8; 4];
+        block[..chunk.len()].copy_from_slice(chunk);
+        let mut value = u32::from_be_bytes(block);
+
+        if zero_group && chunk.len() == 4 && value == 0 {
+            ret.push('z');
+            continue;
+        }
+
+        let mut digits = [0u8; 5];
+        for digit in digits.iter_mut().rev() {
+            *digit = alphabet[(value % 85) as usize];
+            value /= 85;
+        }
+        ret.extend(digits[..chunk.len() + 1].iter().map(|&b| b as char));
+    }
+    ret
+}
+
+/// base85_encode 的逆过程，不足 5 个字符的分组用最大的字符补齐，再取前 n - 1 个字节
+fn base85_decode(data: &str, alphabet: &[u8], zero_group: bool) -> Result<Vec<u8>> {
+    let mut lookup = [None; 256];
+    for (i, &c) in alphabet.iter().enumerate() {
+        lookup[c as usize] = Some(i as u64);
+    }
+
+    let mut ret = Vec::with_capacity(data.len() / 5 * 4 + 4);
+    let mut group = Vec::with_capacity(5);
+    for c in data.bytes().filter(|c| !c.is_ascii_whitespace()) {
+        if zero_group && c == b'z' && group.is_empty() {
+            ret.extend_from_slice(&[0u8; 4]);
+            continue;
+        }
+        let digit = lookup[c as usize]
+            .ok_or_else(|| anyhow::anyhow!("Invalid base85 character: {}", c as char))?;
+        group.push(digit);
+        if group.len() == 5 {
+            flush_base85_group(&mut group, &mut ret)?;
+        }
+    }
+    flush_base85_group(&mut group, &mut ret)?;
+
+    Ok(ret)
+}
+
+fn flush_base85_group(group: &mut Vec<u64>, ret: &mut Vec<u8>) -> Result<()> {
+    if group.is_empty() {
+        return Ok(());
+    }
+    if group.len() == 1 {
+        anyhow::bail!("Invalid base85 length");
+    }
+    let n = group.len();
+    group.resize(5, 84);
+    let value = group.iter().fold(0u64, |acc, &d| acc * 85 + d);
+    let value = u32::try_from(value).map_err(|_| anyhow::anyhow!("Invalid base85 group"))?;
+    ret.extend_from_slice(&value.to_be_bytes()[..n - 1]);
+    group.clear();
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -44,15 +494,221 @@ mod tests {
     #[test]
     fn test_process_encode() {
         let input = "Cargo.toml";
-        let format = Base64Format::Standard;
-        assert!(process_encode(input, format).is_ok());
+        let dir = tempfile::tempdir().unwrap();
+        let output = dir.path().join("encode.txt");
+        let encoding = Encoding::Base64;
+        let style = EncodeStyle::default();
+        assert!(process_encode(input, output.to_str().unwrap(), encoding, &style).is_ok());
     }
 
     #[test]
     fn test_process_decode() {
         let input = "fixtures/b64.txt";
-        let format = Base64Format::UrlSafe;
-        assert!(process_decode(input, format).is_ok());
+        let dir = tempfile::tempdir().unwrap();
+        let output = dir.path().join("decode.txt");
+        let encoding = Encoding::Base64Url;
+        let style = DecodeStyle::default();
+        assert!(process_decode(input, output.to_str().unwrap(), encoding, &style).is_ok());
+    }
+
+    #[test]
+    fn test_wrapped_stream_roundtrip() -> Result<()> {
+        // 20 字节是 4 的整数倍，Z85 也可以编码
+        let data: Vec<u8> = (0..20u8).map(|i| i.wrapping_mul(97)).collect();
+        let wrapped = EncodeStyle {
+            wrap: 8,
+            ..Default::default()
+        };
+        for encoding in [
+            Encoding::Base64,
+            Encoding::Base64Url,
+            Encoding::Base58,
+            Encoding::Base58Check,
+            Encoding::Base32,
+            Encoding::Base32Hex,
+            Encoding::Hex,
+            Encoding::Z85,
+            Encoding::Ascii85,
+        ] {
+            let mut encoded = Vec::new();
+            encode_stream(&mut &data[..], &mut encoded, encoding, &wrapped)?;
+            assert!(encoded.contains(&b'\n'), "{}", encoding);
+            let mut decoded = Vec::new();
+            let style = DecodeStyle::default();
+            decode_stream(&mut &encoded[..], &mut decoded, encoding, &style)?;
+            assert_eq!(decoded, data, "{}", encoding);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_base32_padding() -> Result<()> {
+        let no_pad = EncodeStyle {
+            no_pad: true,
+            ..Default::default()
+        };
+        let mut encoded = Vec::new();
+        encode_stream(&mut &b"foobar"[..], &mut encoded, Encoding::Base32, &no_pad)?;
+        assert_eq!(encoded, b"MZXW6YTBOI\n");
+        let mut decoded = Vec::new();
+        let decode_no_pad = DecodeStyle {
+            no_pad: true,
+            ..Default::default()
+        };
+        decode_stream(
+            &mut &encoded[..],
+            &mut decoded,
+            Encoding::Base32,
+            &decode_no_pad,
+        )?;
+        assert_eq!(decoded, b"foobar");
+
+        // 没有 padding 的编码不能静默忽略 --no-pad
+        let mut encoded = Vec::new();
+        assert!(encode_stream(&mut &b"foobar"[..], &mut encoded, Encoding::Hex, &no_pad).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_stream_binary_roundtrip() -> Result<()> {
+        // 非 UTF-8 的二进制数据，长度超过 io::copy 的缓冲区
+        let data: Vec<u8> = (0..100_000u32).map(|i| (i * 7 % 256) as u8).collect();
+        for encoding in [Encoding::Base64, Encoding::Base64Url, Encoding::Hex] {
+            let mut encoded = Vec::new();
+            encode_stream(
+                &mut &data[..],
+                &mut encoded,
+                encoding,
+                &EncodeStyle::default(),
+            )?;
+            assert_eq!(
+                encoded,
+                format!("{}\n", encode(&data, encoding)?).into_bytes()
+            );
+
+            let mut decoded = Vec::new();
+            decode_stream(
+                &mut &encoded[..],
+                &mut decoded,
+                encoding,
+                &DecodeStyle::default(),
+            )?;
+            assert_eq!(decoded, data);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_stream_skips_whitespace() -> Result<()> {
+        let mut decoded = Vec::new();
+        let style = DecodeStyle::default();
+        decode_stream(
+            &mut &b"Zm9v\nYmFy\r\n\n"[..],
+            &mut decoded,
+            Encoding::Base64,
+            &style,
+        )?;
+        assert_eq!(decoded, b"foobar");
+
+        let ret = decode_stream(
+            &mut &b"Zm9v!"[..],
+            &mut Vec::new(),
+            Encoding::Base64,
+            &style,
+        );
+        assert!(ret.is_err());
+        Ok(())
+    }
+
+    fn encode_with(data: &[u8], encoding: Encoding, style: EncodeStyle) -> Result<String> {
+        let mut encoded = Vec::new();
+        encode_stream(&mut &data[..], &mut encoded, encoding, &style)?;
+        Ok(String::from_utf8(encoded)?)
+    }
+
+    fn decode_with(data: &str, encoding: Encoding, style: DecodeStyle) -> Result<Vec<u8>> {
+        let mut decoded = Vec::new();
+        decode_stream(&mut data.as_bytes(), &mut decoded, encoding, &style)?;
+        Ok(decoded)
+    }
+
+    #[test]
+    fn test_encode_padding() -> Result<()> {
+        let pad = EncodeStyle {
+            pad: true,
+            ..Default::default()
+        };
+        let no_pad = EncodeStyle {
+            no_pad: true,
+            ..Default::default()
+        };
+        let default = EncodeStyle::default();
+
+        assert_eq!(encode_with(b"f", Encoding::Base64, default)?, "Zg==\n");
+        assert_eq!(encode_with(b"f", Encoding::Base64, no_pad)?, "Zg\n");
+        assert_eq!(encode_with(b"f", Encoding::Base64Url, default)?, "Zg\n");
+        assert_eq!(encode_with(b"f", Encoding::Base64Url, pad)?, "Zg==\n");
+        Ok(())
+    }
+
+    #[test]
+    fn test_encode_wrap() -> Result<()> {
+        let data = vec![0u8; 114];
+        let style = EncodeStyle {
+            wrap: 76,
+            ..Default::default()
+        };
+        // 114 字节编码后正好 152 个字符，两行，结尾没有空行
+        let encoded = encode_with(&data, Encoding::Base64, style)?;
+        let lines: Vec<&str> = encoded.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines.iter().all(|l| l.len() == 76));
+        assert!(!encoded.ends_with("\n\n"));
+
+        assert_eq!(
+            decode_with(&encoded, Encoding::Base64, DecodeStyle::default())?,
+            data
+        );
+
+        let style = EncodeStyle {
+            wrap: 4,
+            ..Default::default()
+        };
+        assert_eq!(
+            encode_with(b"foobar", Encoding::Hex, style)?,
+            "666f\n6f62\n6172\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_lenient_and_strict() -> Result<()> {
+        let lenient = DecodeStyle::default();
+        let strict = DecodeStyle {
+            strict: true,
+            ..Default::default()
+        };
+
+        // 宽松模式下带不带 padding 都可以
+        assert_eq!(decode_with("Zg==", Encoding::Base64, lenient)?, b"f");
+        assert_eq!(decode_with("Zg", Encoding::Base64, lenient)?, b"f");
+        assert_eq!(decode_with("Zg==", Encoding::Base64Url, lenient)?, b"f");
+        // 多余的 bit 不为 0
+        assert_eq!(decode_with("Zh==", Encoding::Base64, lenient)?, b"f");
+
+        assert_eq!(decode_with("Zg==", Encoding::Base64, strict)?, b"f");
+        assert!(decode_with("Zg", Encoding::Base64, strict).is_err());
+        assert!(decode_with("Zh==", Encoding::Base64, strict).is_err());
+        assert!(decode_with("Zg==", Encoding::Base64Url, strict).is_err());
+
+        let strict_no_pad = DecodeStyle {
+            no_pad: true,
+            strict: true,
+            ..Default::default()
+        };
+        assert_eq!(decode_with("Zg", Encoding::Base64, strict_no_pad)?, b"f");
+        assert!(decode_with("Zg==", Encoding::Base64, strict_no_pad).is_err());
+        Ok(())
     }
 
     #[test]
@@ -65,4 +721,145 @@ mod tests {
         let decode = BASE64_STANDARD.decode(encode.as_bytes()).unwrap();
         println!("{:?}", String::from_utf8(decode).unwrap());
     }
+
+    #[test]
+    fn test_encoding_vectors() -> Result<()> {
+        let cases: [(Encoding, &[u8], &str); 11] = [
+            (Encoding::Base64, b"foobar", "Zm9vYmFy"),
+            (Encoding::Base64Url, &[0xfb, 0xff], "-_8"),
+            (Encoding::Base58, b"hello world", "StV1DL6CwTryKyV"),
+            (
+                Encoding::Base58Check,
+                b"hello world",
+                "3vQB7B6MrGQZaxCuFg4oh",
+            ),
+            (Encoding::Base32, b"foobar", "MZXW6YTBOI======"),
+            (Encoding::Base32Hex, b"foobar", "CPNMUOJ1E8======"),
+            (Encoding::Hex, b"foobar", "666f6f626172"),
+            // https://rfc.zeromq.org/spec/32/
+            (
+                Encoding::Z85,
+                &[0x86, 0x4F, 0xD2, 0x6F, 0xB5, 0x59, 0xF7, 0x5B],
+                "HelloWorld",
+            ),
+            (Encoding::Ascii85, b"Man ", "9jqo^"),
+            (Encoding::Ascii85, b"hello world", "BOu!rD]j7BEbo7"),
+            (Encoding::Ascii85, b"\0\0\0\0abc", "z@:E^"),
+        ];
+
+        for (encoding, data, encoded) in cases {
+            assert_eq!(encode(data, encoding)?, encoded, "{}", encoding);
+            assert_eq!(decode(encoded, encoding)?, data, "{}", encoding);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_z85_requires_full_groups() {
+        // Z85 只接受 4 字节整数倍的数据，不完整的分组直接报错
+        assert!(encode(&[0x86, 0x4F, 0xD2], Encoding::Z85).is_err());
+        assert!(encode(&[0x86, 0x4F, 0xD2, 0x6F, 0xB5], Encoding::Z85).is_err());
+        assert!(decode("Hello Worl", Encoding::Z85).is_err());
+    }
+
+    #[test]
+    fn test_decode_errors() {
+        assert!(decode("3vQB7B6MrGQZaxCuFg4oi", Encoding::Base58Check).is_err());
+        assert!(decode("0OIl", Encoding::Base58).is_err());
+        assert!(decode("abc", Encoding::Hex).is_err());
+        assert!(decode("H", Encoding::Z85).is_err());
+        assert!(decode("HelloWorl", Encoding::Z85).is_err());
+        assert!(decode("~~~~~", Encoding::Ascii85).is_err());
+    }
+
+    #[test]
+    fn test_decode_lenient_forms() -> Result<()> {
+        assert_eq!(decode("mzxw6ytboi======", Encoding::Base32)?, b"foobar");
+        assert_eq!(decode("666F6F626172", Encoding::Hex)?, b"foobar");
+        assert_eq!(decode("<~9jqo^~>", Encoding::Ascii85)?, b"Man ");
+        Ok(())
+    }
+
+    #[test]
+    fn test_detect_encoding() {
+        let detect = |data: &str| -> Vec<Encoding> {
+            detect_encoding(data).into_iter().map(|(e, _)| e).collect()
+        };
+        let names = |encodings: Vec<Encoding>| -> Vec<&'static str> {
+            encodings.into_iter().map(Into::into).collect()
+        };
+
+        assert_eq!(names(detect("SGVsbG8=")), ["base64", "base64url"]);
+        assert_eq!(names(detect("-_8")), ["base64url"]);
+        assert_eq!(names(detect("+/8")), ["base64"]);
+        assert_eq!(names(detect("MZXW6YTBOI======")), ["base32"]);
+        assert_eq!(
+            names(detect("StV1DL6CwTryKyV")),
+            ["base58", "base64", "base64url"]
+        );
+        assert_eq!(names(detect("3mJr7AoUCHxNq")), ["base58"]);
+        assert_eq!(
+            names(detect("deadbeef\n")),
+            ["hex", "base32", "base58", "base64", "base64url"]
+        );
+        assert!(detect("not valid!").is_empty());
+        assert!(detect("  ").is_empty());
+    }
+
+    #[test]
+    fn test_process_decode_auto() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let output = dir.path().join("decode-auto.txt");
+        let decoded = process_decode_auto("fixtures/b64.txt", output.to_str().unwrap(), false)?;
+        // 没有 + / - _，base64 和 base64url 解码结果一样，不算歧义
+        assert_eq!(Into::<&str>::into(decoded.encoding), "base64");
+        assert!(decoded.alternatives.is_empty());
+        Ok(())
+    }
+
+    // https://github.com/multiformats/multibase/blob/master/tests/basic.csv
+    #[test]
+    fn test_multibase_vectors() -> Result<()> {
+        let data = b"yes mani !";
+        let cases = [
+            (Encoding::Base58, "z7paNL19xttacUY"),
+            (Encoding::Base32, "bpfsxgidnmfxgsibb"),
+            (Encoding::Base32Hex, "vf5in683dc5n6i811"),
+            (Encoding::Base64, "meWVzIG1hbmkgIQ"),
+            (Encoding::Base64Url, "ueWVzIG1hbmkgIQ"),
+            (Encoding::Hex, "f796573206d616e692021"),
+        ];
+        for (encoding, encoded) in cases {
+            assert_eq!(multibase_encode(data, encoding)?, encoded);
+            let (decoded_encoding, decoded) = multibase_decode(encoded)?;
+            assert_eq!(decoded, data);
+            assert_eq!(decoded_encoding.to_string(), encoding.to_string());
+        }
+
+        for encoded in [
+            "BPFSXGIDNMFXGSIBB",
+            "MeWVzIG1hbmkgIQ==",
+            "F796573206D616E692021",
+        ] {
+            assert_eq!(multibase_decode(encoded)?.1, data);
+        }
+        assert!(multibase_decode("xabc").is_err());
+        assert!(multibase_encode(data, Encoding::Z85).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_base85_roundtrip() -> Result<()> {
+        for len in 0..16 {
+            let data: Vec<u8> = (0..len).map(|i| (i * 37 + 255) as u8).collect();
+            assert_eq!(
+                decode(&encode(&data, Encoding::Ascii85)?, Encoding::Ascii85)?,
+                data
+            );
+            if data.len().is_multiple_of(4) {
+                assert_eq!(decode(&encode(&data, Encoding::Z85)?, Encoding::Z85)?, data);
+            }
+        }
+        Ok(())
+    }
 }
@@ -85,7 +85,7 @@ mod tests {
         let a = [1, 2, 3];
         let b = [4, 5, 6];
 
-        let c = a.into_iter().zip(b.into_iter()).collect::<Vec<_>>();
+        let c = a.into_iter().zip(b).collect::<Vec<_>>();
         println!("{:?}", c);
 
         // headers = StringRecord(["Name", "Position", "DOB", "Nationality", "Kit Number"])
@@ -0,0 +1,571 @@
+use std::fs;
+use std::io::{Read, Write};
+
+use anyhow::Result;
+use argon2::{Algorithm, Argon2, Params, Version};
+use chacha20poly1305::aead::stream::{DecryptorBE32, EncryptorBE32};
+use chacha20poly1305::aead::{KeyInit, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, XChaCha20Poly1305};
+use rand::rngs::OsRng;
+use rand::RngCore;
+use zeroize::Zeroizing;
+
+use super::text::{Blake3, KeyLoader};
+use crate::{get_reader, get_writer, read_new_secret, read_secret, EncryptionSecret, TextCipher};
+
+/// 文件格式：
+///
+/// magic "RCLIENC" | version | cipher | kdf | [argon2 m t p | salt] | chunk size | nonce prefix
+/// 之后是若干个 chunk，每个 chunk 为 chunk size 字节的明文加上 16 字节的 tag
+///
+/// 每个 chunk 使用 STREAM 构造（nonce = prefix || 32 位计数器 || 是否为最后一块），
+/// 可以检测到 chunk 被删除、重排和截断；header 作为每个 chunk 的 AAD，修改 header 也会解密失败
+const MAGIC: &[u8; 7] = b"RCLIENC";
+const VERSION: u8 = 1;
+const CHUNK_SIZE: u32 = 64 * 1024;
+// 防止被篡改的 header 导致申请过大的内存
+const MAX_CHUNK_SIZE: u32 = 16 * 1024 * 1024;
+// Argon2 的参数同样来自 header，限制上限，防止恶意文件消耗大量 CPU 和内存
+const MAX_ARGON2_M_COST: u32 = 1024 * 1024;
+const MAX_ARGON2_T_COST: u32 = 16;
+const MAX_ARGON2_P_COST: u32 = 16;
+const TAG_SIZE: usize = 16;
+const SALT_SIZE: usize = 16;
+
+const KDF_NONE: u8 = 0;
+const KDF_ARGON2ID: u8 = 1;
+
+pub fn process_text_encrypt(
+    input: &str,
+    output: &str,
+    cipher: TextCipher,
+    secret: &EncryptionSecret,
+) -> Result<()> {
+    let kdf = match secret.key {
+        Some(_) => None,
+        None => Some(KdfParams::random()),
+    };
+    let mut nonce_prefix = vec![0u8; nonce_prefix_len(cipher)];
+    OsRng.fill_bytes(&mut nonce_prefix);
+    let header = EncryptionHeader {
+        cipher,
+        kdf,
+        chunk_size: CHUNK_SIZE,
+        nonce_prefix,
+    };
+    let key = load_key(secret, header.kdf.as_ref(), true)?;
+
+    let mut reader = get_reader(input)?;
+    let mut writer = get_writer(output)?;
+    encrypt_stream(&mut reader, &mut writer, &header, &key)?;
+    writer.flush()?;
+
+    Ok(())
+}
+
+pub fn process_text_decrypt(input: &str, output: &str, secret: &EncryptionSecret) -> Result<()> {
+    let mut reader = get_reader(input)?;
+    let header = EncryptionHeader::read_from(&mut reader)?;
+    let key = load_key(secret, header.kdf.as_ref(), false)?;
+
+    let mut writer = get_writer(output)?;
+    let ret = decrypt_stream(&mut reader, &mut writer, &header, &key).and_then(|_| {
+        writer.flush()?;
+        Ok(())
+    });
+    // 解密失败时不留下只有一部分的明文文件
+    if ret.is_err() && output != "-" {
+        drop(writer);
+        let _ = fs::remove_file(output);
+    }
+
+    ret
+}
+
+#[derive(Debug)]
+pub(crate) struct KdfParams {
+    m_cost: u32,
+    t_cost: u32,
+    p_cost: u32,
+    salt: [u8; SALT_SIZE],
+}
+
+impl KdfParams {
+    // Argon2 的默认参数加随机 salt
+    pub(crate) fn random() -> Self {
+        let mut salt = [0u8; SALT_SIZE];
+        OsRng.fill_bytes(&mut salt);
+        let params = Params::default();
+        Self {
+            m_cost: params.m_cost(),
+            t_cost: params.t_cost(),
+            p_cost: params.p_cost(),
+            salt,
+        }
+    }
+
+    // m t p 各 4 字节大端序，之后是 salt
+    pub(crate) fn write_to(&self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(&self.m_cost.to_be_bytes());
+        buf.extend_from_slice(&self.t_cost.to_be_bytes());
+        buf.extend_from_slice(&self.p_cost.to_be_bytes());
+        buf.extend_from_slice(&self.salt);
+    }
+
+    pub(crate) fn read_from(reader: &mut dyn Read) -> Result<Self> {
+        let m_cost = u32::from_be_bytes(read_array(reader)?);
+        let t_cost = u32::from_be_bytes(read_array(reader)?);
+        let p_cost = u32::from_be_bytes(read_array(reader)?);
+        let salt = read_array(reader)?;
+        if m_cost > MAX_ARGON2_M_COST {
+            anyhow::bail!("Argon2 memory cost {} KiB is too large", m_cost);
+        }
+        if t_cost > MAX_ARGON2_T_COST {
+            anyhow::bail!("Argon2 time cost {} is too large", t_cost);
+        }
+        if p_cost > MAX_ARGON2_P_COST {
+            anyhow::bail!("Argon2 parallelism {} is too large", p_cost);
+        }
+        Ok(Self {
+            m_cost,
+            t_cost,
+            p_cost,
+            salt,
+        })
+    }
+}
+
+#[derive(Debug)]
+struct EncryptionHeader {
+    cipher: TextCipher,
+    kdf: Option<KdfParams>,
+    chunk_size: u32,
+    nonce_prefix: Vec<u8>,
+}
+
+impl EncryptionHeader {
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend_from_slice(MAGIC);
+        buf.push(VERSION);
+        buf.push(match self.cipher {
+            TextCipher::ChaCha20Poly1305 => 1,
+            TextCipher::XChaCha20Poly1305 => 2,
+        });
+        match &self.kdf {
+            None => buf.push(KDF_NONE),
+            Some(kdf) => {
+                buf.push(KDF_ARGON2ID);
+                kdf.write_to(&mut buf);
+            }
+        }
+        buf.extend_from_slice(&self.chunk_size.to_be_bytes());
+        buf.extend_from_slice(&self.nonce_prefix);
+        buf
+    }
+
+    fn read_from(reader: &mut dyn Read) -> Result<Self> {
+        let mut magic = [0u8; 7];
+        reader
+            .read_exact(&mut magic)
+            .map_err(|_| anyhow::anyhow!("Input is too short to be an encrypted file"))?;
+        if &magic != MAGIC {
+            anyhow::bail!("Input is not a file encrypted by rcli text encrypt");
+        }
+
+        let [version, cipher, kdf] = read_array(reader)?;
+        if version != VERSION {
+            anyhow::bail!("Unsupported encryption format version: {}", version);
+        }
+        let cipher = match cipher {
+            1 => TextCipher::ChaCha20Poly1305,
+            2 => TextCipher::XChaCha20Poly1305,
+            _ => anyhow::bail!("Unknown cipher id: {}", cipher),
+        };
+        let kdf = match kdf {
+            KDF_NONE => None,
+            KDF_ARGON2ID => Some(KdfParams::read_from(reader)?),
+            _ => anyhow::bail!("Unknown key derivation id: {}", kdf),
+        };
+        let chunk_size = u32::from_be_bytes(read_array(reader)?);
+        if chunk_size == 0 || chunk_size > MAX_CHUNK_SIZE {
+            anyhow::bail!("Invalid chunk size: {}", chunk_size);
+        }
+        let mut nonce_prefix = vec![0u8; nonce_prefix_len(cipher)];
+        reader.read_exact(&mut nonce_prefix)?;
+
+        Ok(Self {
+            cipher,
+            kdf,
+            chunk_size,
+            nonce_prefix,
+        })
+    }
+}
+
+// 两种 cipher 的 STREAM 类型不同，用 enum 包一层，避免到处写泛型约束
+enum StreamEncryptor {
+    ChaCha(EncryptorBE32<ChaCha20Poly1305>),
+    XChaCha(EncryptorBE32<XChaCha20Poly1305>),
+}
+
+enum StreamDecryptor {
+    ChaCha(DecryptorBE32<ChaCha20Poly1305>),
+    XChaCha(DecryptorBE32<XChaCha20Poly1305>),
+}
+
+impl StreamEncryptor {
+    fn new(header: &EncryptionHeader, key: &[u8; 32]) -> Self {
+        let prefix = header.nonce_prefix.as_slice();
+        match header.cipher {
+            TextCipher::ChaCha20Poly1305 => Self::ChaCha(EncryptorBE32::from_aead(
+                ChaCha20Poly1305::new(key.into()),
+                prefix.into(),
+            )),
+            TextCipher::XChaCha20Poly1305 => Self::XChaCha(EncryptorBE32::from_aead(
+                XChaCha20Poly1305::new(key.into()),
+                prefix.into(),
+            )),
+        }
+    }
+
+    fn next(&mut self, msg: &[u8], aad: &[u8]) -> Result<Vec<u8>> {
+        let payload = Payload { msg, aad };
+        let ret = match self {
+            Self::ChaCha(e) => e.encrypt_next(payload),
+            Self::XChaCha(e) => e.encrypt_next(payload),
+        };
+        ret.map_err(|_| anyhow::anyhow!("Encryption failed"))
+    }
+
+    fn last(self, msg: &[u8], aad: &[u8]) -> Result<Vec<u8>> {
+        let payload = Payload { msg, aad };
+        let ret = match self {
+            Self::ChaCha(e) => e.encrypt_last(payload),
+            Self::XChaCha(e) => e.encrypt_last(payload),
+        };
+        ret.map_err(|_| anyhow::anyhow!("Encryption failed"))
+    }
+}
+
+impl StreamDecryptor {
+    fn new(header: &EncryptionHeader, key: &[u8; 32]) -> Self {
+        let prefix = header.nonce_prefix.as_slice();
+        match header.cipher {
+            TextCipher::ChaCha20Poly1305 => Self::ChaCha(DecryptorBE32::from_aead(
+                ChaCha20Poly1305::new(key.into()),
+                prefix.into(),
+            )),
+            TextCipher::XChaCha20Poly1305 => Self::XChaCha(DecryptorBE32::from_aead(
+                XChaCha20Poly1305::new(key.into()),
+                prefix.into(),
+            )),
+        }
+    }
+
+    fn next(&mut self, msg: &[u8], aad: &[u8]) -> Result<Vec<u8>> {
+        let payload = Payload { msg, aad };
+        let ret = match self {
+            Self::ChaCha(d) => d.decrypt_next(payload),
+            Self::XChaCha(d) => d.decrypt_next(payload),
+        };
+        ret.map_err(|_| anyhow::anyhow!("Decryption failed: wrong key or corrupted data"))
+    }
+
+    fn last(self, msg: &[u8], aad: &[u8]) -> Result<Vec<u8>> {
+        let payload = Payload { msg, aad };
+        let ret = match self {
+            Self::ChaCha(d) => d.decrypt_last(payload),
+            Self::XChaCha(d) => d.decrypt_last(payload),
+        };
+        ret.map_err(|_| {
+            anyhow::anyhow!("Decryption failed: wrong key, corrupted or truncated data")
+        })
+    }
+}
+
+/// 多读一块来判断当前块是不是最后一块，最后一块可以为空（例如输入为空）
+fn encrypt_stream(
+    reader: &mut dyn Read,
+    writer: &mut dyn Write,
+    header: &EncryptionHeader,
+    key: &[u8; 32],
+) -> Result<()> {
+    let aad = header.to_bytes();
+    writer.write_all(&aad)?;
+
+    let mut encryptor = StreamEncryptor::new(header, key);
+    let chunk_size = header.chunk_size as usize;
+    let mut current = vec![0u8; chunk_size];
+    let mut next = vec![0u8; chunk_size];
+    let mut n = read_full(reader, &mut current)?;
+    loop {
+        let m = if n == chunk_size {
+            read_full(reader, &mut next)?
+        } else {
+            0
+        };
+        if m == 0 {
+            writer.write_all(&encryptor.last(&current[..n], &aad)?)?;
+            return Ok(());
+        }
+        writer.write_all(&encryptor.next(&current[..n], &aad)?)?;
+        std::mem::swap(&mut current, &mut next);
+        n = m;
+    }
+}
+
+fn decrypt_stream(
+    reader: &mut dyn Read,
+    writer: &mut dyn Write,
+    header: &EncryptionHeader,
+    key: &[u8; 32],
+) -> Result<()> {
+    let aad = header.to_bytes();
+    let mut decryptor = StreamDecryptor::new(header, key);
+    let chunk_size = header.chunk_size as usize + TAG_SIZE;
+    let mut current = vec![0u8; chunk_size];
+    let mut next = vec![0u8; chunk_size];
+    let mut n = read_full(reader, &mut current)?;
+    loop {
+        let m = if n == chunk_size {
+            read_full(reader, &mut next)?
+        } else {
+            0
+        };
+        if m == 0 {
+            writer.write_all(&decryptor.last(&current[..n], &aad)?)?;
+            return Ok(());
+        }
+        writer.write_all(&decryptor.next(&current[..n], &aad)?)?;
+        std::mem::swap(&mut current, &mut next);
+        n = m;
+    }
+}
+
+fn load_key(
+    secret: &EncryptionSecret,
+    kdf: Option<&KdfParams>,
+    confirm: bool,
+) -> Result<Zeroizing<[u8; 32]>> {
+    match (&secret.key, kdf) {
+        (Some(path), None) => Ok(Zeroizing::new(Blake3::load(path)?.key)),
+        (Some(_), Some(_)) => anyhow::bail!("File was encrypted with a passphrase, not a key file"),
+        (None, None) => anyhow::bail!("File was encrypted with a key file, not a passphrase"),
+        (None, Some(kdf)) => {
+            let passphrase = read_passphrase(secret, confirm)?;
+            derive_key(&passphrase, kdf)
+        }
+    }
+}
+
+fn read_passphrase(secret: &EncryptionSecret, confirm: bool) -> Result<Zeroizing<Vec<u8>>> {
+    let path = secret.passphrase_file.as_deref();
+    // 加密时在终端中输入两次
+    if confirm {
+        read_new_secret(path, "Passphrase: ")
+    } else {
+        read_secret(path, "Passphrase: ")
+    }
+}
+
+pub(crate) fn derive_key(passphrase: &[u8], kdf: &KdfParams) -> Result<Zeroizing<[u8; 32]>> {
+    let params = Params::new(kdf.m_cost, kdf.t_cost, kdf.p_cost, Some(32))
+        .map_err(|e| anyhow::anyhow!("Invalid Argon2 parameters: {}", e))?;
+    let mut key = Zeroizing::new([0u8; 32]);
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password_into(passphrase, &kdf.salt, key.as_mut())
+        .map_err(|e| anyhow::anyhow!("Argon2 failed: {}", e))?;
+
+    Ok(key)
+}
+
+// 96 位 nonce 留 7 字节随机前缀，192 位 nonce 留 19 字节，后 5 字节由 STREAM 使用
+fn nonce_prefix_len(cipher: TextCipher) -> usize {
+    match cipher {
+        TextCipher::ChaCha20Poly1305 => 7,
+        TextCipher::XChaCha20Poly1305 => 19,
+    }
+}
+
+fn read_array<const N: usize>(reader: &mut dyn Read) -> Result<[u8; N]> {
+    let mut buf = [0u8; N];
+    reader
+        .read_exact(&mut buf)
+        .map_err(|_| anyhow::anyhow!("Encrypted file header is truncated"))?;
+    Ok(buf)
+}
+
+// 和 read_exact 不同，遇到 EOF 时返回已经读到的字节数
+pub(crate) fn read_full(reader: &mut dyn Read, buf: &mut [u8]) -> Result<usize> {
+    let mut n = 0;
+    while n < buf.len() {
+        match reader.read(&mut buf[n..])? {
+            0 => break,
+            m => n += m,
+        }
+    }
+    Ok(n)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header(cipher: TextCipher, chunk_size: u32) -> EncryptionHeader {
+        EncryptionHeader {
+            cipher,
+            kdf: None,
+            chunk_size,
+            nonce_prefix: vec![7u8; nonce_prefix_len(cipher)],
+        }
+    }
+
+    fn encrypt(data: &[u8], header: &EncryptionHeader, key: &[u8; 32]) -> Result<Vec<u8>> {
+        let mut out = Vec::new();
+        encrypt_stream(&mut &data[..], &mut out, header, key)?;
+        Ok(out)
+    }
+
+    fn decrypt(data: &[u8], key: &[u8; 32]) -> Result<Vec<u8>> {
+        let mut reader = data;
+        let header = EncryptionHeader::read_from(&mut reader)?;
+        let mut out = Vec::new();
+        decrypt_stream(&mut reader, &mut out, &header, key)?;
+        Ok(out)
+    }
+
+    #[test]
+    fn test_encrypt_decrypt_roundtrip() -> Result<()> {
+        let key = [42u8; 32];
+        for cipher in [TextCipher::ChaCha20Poly1305, TextCipher::XChaCha20Poly1305] {
+            // 覆盖空输入、不足一块、正好整块和多块的情况
+            for len in [0, 1, 15, 16, 17, 64, 100] {
+                let data: Vec<u8> = (0..len).map(|i| i as u8).collect();
+                let encrypted = encrypt(&data, &header(cipher, 16), &key)?;
+                assert_eq!(decrypt(&encrypted, &key)?, data);
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_decrypt_detects_tampering() -> Result<()> {
+        let key = [42u8; 32];
+        let data = vec![1u8; 50];
+        let header = header(TextCipher::XChaCha20Poly1305, 16);
+        let encrypted = encrypt(&data, &header, &key)?;
+        let header_len = header.to_bytes().len();
+
+        assert!(decrypt(&encrypted, &[0u8; 32]).is_err());
+
+        let mut flipped = encrypted.clone();
+        flipped[header_len + 3] ^= 1;
+        assert!(decrypt(&flipped, &key).is_err());
+
+        // 修改 header 中的 nonce 前缀
+        let mut flipped = encrypted.clone();
+        flipped[header_len - 1] ^= 1;
+        assert!(decrypt(&flipped, &key).is_err());
+
+        // 去掉最后一块，或者在块的边界截断
+        let chunk = 16 + TAG_SIZE;
+        assert!(decrypt(&encrypted[..encrypted.len() - 5], &key).is_err());
+        assert!(decrypt(&encrypted[..header_len + 2 * chunk], &key).is_err());
+
+        // 交换前两块
+        let mut swapped = encrypted[..header_len].to_vec();
+        swapped.extend_from_slice(&encrypted[header_len + chunk..header_len + 2 * chunk]);
+        swapped.extend_from_slice(&encrypted[header_len..header_len + chunk]);
+        swapped.extend_from_slice(&encrypted[header_len + 2 * chunk..]);
+        assert!(decrypt(&swapped, &key).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_header_roundtrip() -> Result<()> {
+        let header = EncryptionHeader {
+            cipher: TextCipher::ChaCha20Poly1305,
+            kdf: Some(KdfParams {
+                m_cost: 19456,
+                t_cost: 2,
+                p_cost: 1,
+                salt: [9u8; SALT_SIZE],
+            }),
+            chunk_size: CHUNK_SIZE,
+            nonce_prefix: vec![1u8; 7],
+        };
+        let bytes = header.to_bytes();
+        assert_eq!(&bytes[..8], b"RCLIENC\x01");
+        let parsed = EncryptionHeader::read_from(&mut &bytes[..])?;
+        assert_eq!(parsed.to_bytes(), bytes);
+
+        assert!(EncryptionHeader::read_from(&mut &b"NOTRCLI\x01"[..]).is_err());
+        let mut future = bytes.clone();
+        future[7] = 2;
+        assert!(EncryptionHeader::read_from(&mut &future[..]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_kdf_params_limits() -> Result<()> {
+        let params = |m_cost, t_cost, p_cost| {
+            let mut buf = Vec::new();
+            KdfParams {
+                m_cost,
+                t_cost,
+                p_cost,
+                salt: [0u8; SALT_SIZE],
+            }
+            .write_to(&mut buf);
+            buf
+        };
+        KdfParams::read_from(&mut &params(19456, 2, 1)[..])?;
+        for (m_cost, t_cost, p_cost) in [
+            (MAX_ARGON2_M_COST + 1, 2, 1),
+            (19456, MAX_ARGON2_T_COST + 1, 1),
+            (19456, 2, MAX_ARGON2_P_COST + 1),
+            (19456, u32::MAX, u32::MAX),
+        ] {
+            assert!(KdfParams::read_from(&mut &params(m_cost, t_cost, p_cost)[..]).is_err());
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_passphrase_roundtrip() -> Result<()> {
+        let tmp = tempfile::tempdir()?;
+        let dir = tmp.path();
+        let passphrase = dir.join("passphrase.txt");
+        fs::write(&passphrase, "correct horse battery staple\n")?;
+        let encrypted = dir.join("hello.enc");
+        let decrypted = dir.join("hello.dec");
+        let secret = EncryptionSecret {
+            key: None,
+            passphrase: false,
+            passphrase_file: Some(passphrase.to_str().unwrap().to_string()),
+        };
+
+        process_text_encrypt(
+            "fixtures/hello.txt",
+            encrypted.to_str().unwrap(),
+            TextCipher::XChaCha20Poly1305,
+            &secret,
+        )?;
+        process_text_decrypt(
+            encrypted.to_str().unwrap(),
+            decrypted.to_str().unwrap(),
+            &secret,
+        )?;
+        assert_eq!(fs::read(&decrypted)?, fs::read("fixtures/hello.txt")?);
+
+        // 用 key 文件解密口令加密的文件，直接报错
+        let key_secret = EncryptionSecret {
+            key: Some("fixtures/blake3.txt".to_string()),
+            passphrase: false,
+            passphrase_file: None,
+        };
+        assert!(process_text_decrypt(encrypted.to_str().unwrap(), "-", &key_secret).is_err());
+        Ok(())
+    }
+}
@@ -1,56 +1,741 @@
-// 这个 trait 是在 rand::seq 中为 [T] 实现的，所以需要 import 到这里
-use rand::seq::SliceRandom;
+use anyhow::Result;
+use argon2::Argon2;
+use hkdf::Hkdf;
+use rand::rngs::OsRng;
+use rand::{Rng, RngCore, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+use serde::Serialize;
+use sha2::Sha256;
+use zeroize::{Zeroize, Zeroizing};
+use zxcvbn::zxcvbn;
+
+use crate::{CodeGroups, GenPassFormat, PasswordPolicy, RngSource};
 
 // const 类型必须要指定，这里也不用指定 生命周期为 'static ，
-const UPPER: &'static [u8] = b"ABCDEFGHGKLMNPQRSTUVWXYZ";
-const LOWER: &[u8] = b"abcdefghigkmnopqrstuvwxyz";
+// 大写字母不含 I O，小写字母不含 l，数字不含 0
+const UPPER: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ";
+const LOWER: &[u8] = b"abcdefghijkmnopqrstuvwxyz";
 const NUMBER: &[u8] = b"123456789";
 const SYMBOL: &[u8] = b"!@#$%^&*_";
+const CLASSES: [&[u8]; 4] = [UPPER, LOWER, NUMBER, SYMBOL];
+
+// 可发音密码使用的辅音和元音，去掉了电话里容易听错的 c q x y l
+const CONSONANT: &[u8] = b"bdfghjkmnprstvwz";
+const VOWEL: &[u8] = b"aeiou";
+
+// Crockford base32，去掉了 I L O U，不区分大小写，适合人工抄写
+const CROCKFORD: &[u8] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+const DIGIT: &[u8] = b"0123456789";
+
+// --seed 经过 blake3 derive_key 得到 ChaCha20 的 key
+const SEED_CONTEXT: &str = "rcli genpass chacha seed v1";
+
+// derive 模式的 domain separation，修改后所有派生出的密码都会变化
+const DERIVE_CONTEXT: &str = "rcli.genpass.derive.v1";
+// derive v1 固定使用的字母表，不随上面的 UPPER / LOWER 等变化
+// 修改这里会改变所有已经派生出的密码，需要同时升级 DERIVE_CONTEXT 的版本
+const DERIVE_V1_CLASSES: [&[u8]; 4] = [
+    b"ABCDEFGHJKLMNPQRSTUVWXYZ",
+    b"abcdefghijkmnopqrstuvwxyz",
+    b"123456789",
+    b"!@#$%^&*_",
+];
+
+impl PasswordPolicy {
+    /// 启用的字符类别
+    pub fn classes(&self) -> Vec<&'static [u8]> {
+        self.classes_from(CLASSES)
+    }
+
+    // 从 大写 / 小写 / 数字 / 符号 四个字母表中选出启用的类别
+    fn classes_from(&self, all: [&'static [u8]; 4]) -> Vec<&'static [u8]> {
+        [self.uppercase, self.lowercase, self.number, self.symbol]
+            .into_iter()
+            .zip(all)
+            .filter_map(|(enabled, class)| enabled.then_some(class))
+            .collect()
+    }
+
+    /// 所有启用类别合并后的字母表
+    pub fn alphabet(&self) -> Vec<u8> {
+        self.classes().concat()
+    }
+
+    /// 完全随机生成时的熵，每个字符都从整个字母表中选取
+    /// 每类至少一个字符的约束会让实际值略低一点，这里忽略
+    pub fn entropy_bits(&self) -> f64 {
+        self.length as f64 * (self.alphabet().len() as f64).log2()
+    }
+
+    /// 可发音密码的熵，和 process_genpass_pronounceable 中的每一次随机选择一一对应
+    pub fn pronounceable_entropy_bits(&self) -> f64 {
+        let letters = (self.length as usize).saturating_sub(self.inserted());
+        let syllables = letters.div_ceil(2);
+        let mut bits = (letters / 2) as f64 * ((CONSONANT.len() * VOWEL.len()) as f64).log2();
+        if letters % 2 == 1 {
+            bits += (CONSONANT.len() as f64).log2();
+        }
+        if self.uppercase && syllables > 0 {
+            bits += (syllables as f64).log2();
+        }
+        if self.number {
+            bits += ((NUMBER.len() * (syllables + 1)) as f64).log2();
+        }
+        if self.symbol {
+            bits += ((SYMBOL.len() * (syllables + 1)) as f64).log2();
+        }
+        bits
+    }
+
+    /// 可发音密码中可能出现的字符数
+    fn pronounceable_alphabet_size(&self) -> usize {
+        let mut size = CONSONANT.len() + VOWEL.len();
+        if self.uppercase {
+            size += CONSONANT.len();
+        }
+        if self.number {
+            size += NUMBER.len();
+        }
+        if self.symbol {
+            size += SYMBOL.len();
+        }
+        size
+    }
+
+    // 可发音密码中插入的数字和符号个数
+    fn inserted(&self) -> usize {
+        self.number as usize + self.symbol as usize
+    }
+
+    /// 指定了 bits 时，计算出满足熵要求的最短长度，返回长度确定的 policy
+    pub fn resolve(&self, pronounceable: bool) -> Result<PasswordPolicy> {
+        let Some(bits) = self.bits else {
+            return Ok(*self);
+        };
+
+        // 没有任何字符类别时无论多长都没有熵，先报告真正的原因
+        if !pronounceable && self.classes().is_empty() {
+            anyhow::bail!("At least one character class must be enabled");
+        }
+        let min = if pronounceable {
+            self.inserted() + 2
+        } else {
+            self.classes().len()
+        };
+        for length in min..=u8::MAX as usize {
+            let policy = PasswordPolicy {
+                length: length as u8,
+                ..*self
+            };
+            let entropy = if pronounceable {
+                policy.pronounceable_entropy_bits()
+            } else {
+                policy.entropy_bits()
+            };
+            if entropy >= bits as f64 {
+                return Ok(policy);
+            }
+        }
+
+        anyhow::bail!(
+            "Cannot reach {} bits of entropy within {} characters",
+            bits,
+            u8::MAX
+        )
+    }
+
+    /// 按照规则生成密码，pick(n) 返回 [0, n) 中均匀分布的下标
+    /// 随机生成和 derive 只是随机源不同，生成规则完全一样
+    fn generate_with(
+        &self,
+        classes: &[&[u8]],
+        mut pick: impl FnMut(usize) -> Result<usize>,
+    ) -> Result<Vec<u8>> {
+        if classes.is_empty() {
+            anyhow::bail!("At least one character class must be enabled");
+        }
+        if (self.length as usize) < classes.len() {
+            anyhow::bail!(
+                "Password length must be at least {} to include every character class",
+                classes.len()
+            );
+        }
+
+        let chars = classes.concat();
+        let mut password = Vec::with_capacity(self.length as usize);
+
+        // 保证每种类型的字符都有一个
+        for class in classes {
+            password.push(class[pick(class.len())?]);
+        }
+
+        while password.len() < self.length as usize {
+            password.push(chars[pick(chars.len())?]);
+        }
+
+        // Fisher-Yates shuffle，和 SliceRandom::shuffle 的做法一样
+        for i in (1..password.len()).rev() {
+            let j = pick(i + 1)?;
+            password.swap(i, j);
+        }
+
+        Ok(password)
+    }
+}
+
+/// 生成的密码及其强度信息，value 在 drop 时会被清零
+/// 只是尽量减少残留：格式化输出、serde_json / csv 的缓冲区等中间结果不会被清零
+pub struct GeneratedPassword {
+    pub value: Zeroizing<String>,
+    pub entropy_bits: f64,
+    pub alphabet_size: usize,
+    // bits 已经被换算成 length 的 policy
+    pub policy: PasswordPolicy,
+}
+
+/// 根据 RngSource 创建随机源
+/// 生产环境总是使用 OsRng；ChaCha20 必须提供 seed，结果可复现，只能用于生成测试数据
+pub fn build_rng(source: RngSource, seed: Option<&str>) -> Result<Box<dyn RngCore>> {
+    match (source, seed) {
+        (RngSource::Os, None) => Ok(Box::new(OsRng)),
+        (RngSource::Os, Some(_)) => anyhow::bail!("--seed can only be used with --rng chacha"),
+        (RngSource::ChaCha, None) => anyhow::bail!("--rng chacha requires --seed"),
+        (RngSource::ChaCha, Some(seed)) => {
+            let bytes = hex::decode(seed)?;
+            if bytes.is_empty() {
+                anyhow::bail!("Seed must not be empty");
+            }
+            // 不直接补 0，否则 01 和 0100 会得到同样的随机流
+            let key = blake3::derive_key(SEED_CONTEXT, &bytes);
+            Ok(Box::new(ChaCha20Rng::from_seed(key)))
+        }
+    }
+}
+
+/// 生成细节的说明，用于 --explain
+pub fn explain_genpass(
+    policy: &PasswordPolicy,
+    pronounceable: bool,
+    source: RngSource,
+) -> Result<String> {
+    let resolved = policy.resolve(pronounceable)?;
+    let (mode, alphabet_size, entropy) = if pronounceable {
+        (
+            "pronounceable",
+            resolved.pronounceable_alphabet_size(),
+            resolved.pronounceable_entropy_bits(),
+        )
+    } else {
+        ("random", resolved.alphabet().len(), resolved.entropy_bits())
+    };
+    let rng = match source {
+        RngSource::Os => "os (operating system CSPRNG)",
+        RngSource::ChaCha => "chacha (seeded ChaCha20, TEST ONLY - output is reproducible)",
+    };
+
+    let mut lines = vec![
+        format!("rng: {}", rng),
+        format!("mode: {}", mode),
+        format!("length: {}", resolved.length),
+        format!("alphabet size: {}", alphabet_size),
+        format!("entropy: {:.2} bits", entropy),
+    ];
+    if let Some(bits) = policy.bits {
+        lines.insert(2, format!("requested entropy: {} bits", bits));
+    }
+
+    Ok(lines.join("\n"))
+}
 
 /// 完全独立于 cli 的代码
 pub fn process_genpass(
-    length: u8,
-    upper: bool,
-    lower: bool,
-    number: bool,
-    symbol: bool,
-) -> anyhow::Result<String> {
-    let mut rng = rand::thread_rng();
-    let mut password = Vec::new();
-    let mut chars = Vec::new();
-
-    if upper {
-        chars.extend_from_slice(UPPER);
-        // 保证每种类型的字符都有一个
-        password.push(*NUMBER.choose(&mut rng).expect("UPPER won't be empty"));
+    policy: &PasswordPolicy,
+    rng: &mut dyn RngCore,
+) -> Result<GeneratedPassword> {
+    let policy = policy.resolve(false)?;
+    let password =
+        Zeroizing::new(policy.generate_with(&policy.classes(), |n| Ok(rng.gen_range(0..n)))?);
+
+    Ok(GeneratedPassword {
+        value: Zeroizing::new(String::from_utf8(password.to_vec())?),
+        entropy_bits: policy.entropy_bits(),
+        alphabet_size: policy.alphabet().len(),
+        policy,
+    })
+}
+
+/// 生成可发音的密码，熵比同样长度的随机密码低
+/// 由 辅音+元音 的音节组成，长度为奇数时以一个辅音结尾，
+/// uppercase 会把随机一个音节的首字母大写，number / symbol 各插入一个到随机的音节边界
+/// 同一边界上 symbol 总是在 number 之后，保证不同的随机选择得到不同的密码，熵可以直接相加
+pub fn process_genpass_pronounceable(
+    policy: &PasswordPolicy,
+    rng: &mut dyn RngCore,
+) -> Result<GeneratedPassword> {
+    let policy = policy.resolve(true)?;
+    let mut pick = |n: usize| rng.gen_range(0..n);
+
+    let letters = (policy.length as usize).saturating_sub(policy.inserted());
+    if letters < 2 {
+        anyhow::bail!(
+            "Password length must be at least {} for a pronounceable password",
+            policy.inserted() + 2
+        );
+    }
+
+    let mut syllables: Vec<Vec<u8>> = (0..letters / 2)
+        .map(|_| vec![CONSONANT[pick(CONSONANT.len())], VOWEL[pick(VOWEL.len())]])
+        .collect();
+    if letters % 2 == 1 {
+        syllables.push(vec![CONSONANT[pick(CONSONANT.len())]]);
     }
 
-    if lower {
-        chars.extend_from_slice(LOWER);
-        password.push(*LOWER.choose(&mut rng).expect("LOWER won't be empty"));
+    if policy.uppercase {
+        let i = pick(syllables.len());
+        syllables[i][0].make_ascii_uppercase();
     }
 
-    if number {
-        chars.extend_from_slice(NUMBER);
-        password.push(*NUMBER.choose(&mut rng).expect("NUMBER won't be empty"));
+    // boundaries[i] 是插入到第 i 个音节之前的字符，最后一个位置在末尾
+    let mut boundaries = vec![Vec::new(); syllables.len() + 1];
+    if policy.number {
+        let c = NUMBER[pick(NUMBER.len())];
+        boundaries[pick(syllables.len() + 1)].push(c);
+    }
+    if policy.symbol {
+        let c = SYMBOL[pick(SYMBOL.len())];
+        boundaries[pick(syllables.len() + 1)].push(c);
     }
 
-    if symbol {
-        chars.extend_from_slice(SYMBOL);
-        password.push(*SYMBOL.choose(&mut rng).expect("SYMBOL won't be empty"));
+    let mut password = String::with_capacity(policy.length as usize);
+    for (i, syllable) in syllables.iter_mut().enumerate() {
+        password.extend(boundaries[i].iter().map(|&b| b as char));
+        password.extend(syllable.iter().map(|&b| b as char));
+        syllable.zeroize();
     }
+    password.extend(boundaries[syllables.len()].iter().map(|&b| b as char));
+    boundaries.zeroize();
 
-    for _ in 0..(length - password.len() as u8) {
-        let c = chars
-            .choose(&mut rng)
-            .expect("chars won't be empty in this context");
-        password.push(*c);
+    Ok(GeneratedPassword {
+        value: Zeroizing::new(password),
+        entropy_bits: policy.pronounceable_entropy_bits(),
+        alphabet_size: policy.pronounceable_alphabet_size(),
+        policy,
+    })
+}
+
+/// 生成数字 PIN，直接使用 OsRng，gen_range 内部做了拒绝采样，没有取模偏差
+pub fn process_genpass_pin(length: u8) -> Result<String> {
+    if length == 0 {
+        anyhow::bail!("PIN length must be positive");
     }
 
-    password.shuffle(&mut rng);
-    // todo: make sure the password has at least one of each type
+    Ok(sample_string(DIGIT, length as usize))
+}
 
-    let password = String::from_utf8(password)?;
+/// 生成一组恢复码，每个恢复码由若干组 Crockford base32 字符组成，组之间用 - 连接
+pub fn process_recovery_codes(count: usize, groups: CodeGroups) -> Result<Vec<String>> {
+    let codes = (0..count)
+        .map(|_| {
+            (0..groups.groups)
+                .map(|_| sample_string(CROCKFORD, groups.size))
+                .collect::<Vec<_>>()
+                .join("-")
+        })
+        .collect();
 
-    Ok(password)
+    Ok(codes)
+}
+
+fn sample_string(alphabet: &[u8], length: usize) -> String {
+    let mut rng = OsRng;
+    (0..length)
+        .map(|_| alphabet[rng.gen_range(0..alphabet.len())] as char)
+        .collect()
+}
+
+/// 由 master secret 派生出站点相关的密码，相同的输入总是得到相同的密码
+/// master secret 先经过 Argon2id 拉伸（salt 与 user 绑定），
+/// 再通过 HKDF 按 site / counter / policy 展开出字节流
+/// 返回的 entropy_bits 是字母表能提供的上限，实际强度还受 master secret 的熵限制
+pub fn process_genpass_derive(
+    master: &[u8],
+    site: &str,
+    user: &str,
+    counter: u32,
+    policy: &PasswordPolicy,
+) -> Result<GeneratedPassword> {
+    let policy = policy.resolve(false)?;
+    let salt = format!("{}:{}", DERIVE_CONTEXT, user);
+    let mut master_key = Zeroizing::new([0u8; 32]);
+    Argon2::default()
+        .hash_password_into(master, salt.as_bytes(), master_key.as_mut())
+        .map_err(|e| anyhow::anyhow!("Argon2 failed: {}", e))?;
+
+    let info = format!(
+        "{}:{}:{}:{}{}{}{}{}",
+        DERIVE_CONTEXT,
+        site.to_lowercase(),
+        counter,
+        policy.length,
+        policy.uppercase as u8,
+        policy.lowercase as u8,
+        policy.number as u8,
+        policy.symbol as u8,
+    );
+    // 远多于生成密码需要的字节数，拒绝采样几乎不可能耗尽
+    let mut stream = Zeroizing::new([0u8; 1024]);
+    Hkdf::<Sha256>::new(None, master_key.as_ref())
+        .expand(info.as_bytes(), stream.as_mut())
+        .map_err(|e| anyhow::anyhow!("HKDF failed: {}", e))?;
+
+    let classes = policy.classes_from(DERIVE_V1_CLASSES);
+    let alphabet_size = classes.concat().len();
+    let mut bytes = stream.iter();
+    let password =
+        Zeroizing::new(policy.generate_with(&classes, |n| pick_unbiased(&mut bytes, n))?);
+
+    Ok(GeneratedPassword {
+        value: Zeroizing::new(String::from_utf8(password.to_vec())?),
+        entropy_bits: policy.length as f64 * (alphabet_size as f64).log2(),
+        alphabet_size,
+        policy,
+    })
+}
+
+/// 拒绝采样：丢弃落在 256 % n 余数区间的字节，避免直接取模带来的偏差
+fn pick_unbiased<'a>(bytes: &mut impl Iterator<Item = &'a u8>, n: usize) -> Result<usize> {
+    if n == 0 || n > 256 {
+        anyhow::bail!("Cannot sample from a range of size {}", n);
+    }
+    let limit = 256 - 256 % n;
+    for &b in bytes {
+        if (b as usize) < limit {
+            return Ok(b as usize % n);
+        }
+    }
+    anyhow::bail!("Derived byte stream exhausted")
+}
+
+#[derive(Debug, Serialize)]
+pub struct LabeledPassword {
+    pub label: String,
+    pub password: Zeroizing<String>,
+    // zxcvbn 评分 0 ~ 4
+    pub strength: u8,
+    pub entropy_bits: f64,
+}
+
+/// 批量生成密码，label 不足时用 password_{n} 补齐
+pub fn process_genpass_batch(
+    count: usize,
+    labels: &[String],
+    policy: &PasswordPolicy,
+    pronounceable: bool,
+    rng: &mut dyn RngCore,
+) -> Result<Vec<LabeledPassword>> {
+    let count = count.max(labels.len());
+    let mut ret = Vec::with_capacity(count);
+
+    for i in 0..count {
+        let label = match labels.get(i) {
+            Some(label) => label.clone(),
+            None => format!("password_{}", i + 1),
+        };
+        let generated = if pronounceable {
+            process_genpass_pronounceable(policy, rng)?
+        } else {
+            process_genpass(policy, rng)?
+        };
+        let strength = zxcvbn(&generated.value, &[])?.score();
+        ret.push(LabeledPassword {
+            label,
+            // 保留两位小数
+            entropy_bits: (generated.entropy_bits * 100.0).round() / 100.0,
+            password: generated.value,
+            strength,
+        });
+    }
+
+    Ok(ret)
+}
+
+pub fn format_passwords(
+    passwords: &[LabeledPassword],
+    format: GenPassFormat,
+) -> Result<Zeroizing<String>> {
+    let content = match format {
+        GenPassFormat::Plain => passwords
+            .iter()
+            .map(|p| format!("{}\n", *p.password))
+            .collect(),
+        GenPassFormat::Json => serde_json::to_string_pretty(passwords)? + "\n",
+        GenPassFormat::Csv => {
+            let mut writer = csv::Writer::from_writer(Vec::new());
+            for p in passwords {
+                writer.serialize(p)?;
+            }
+            String::from_utf8(writer.into_inner()?)?
+        }
+        GenPassFormat::Env => {
+            let mut content = String::new();
+            for p in passwords {
+                if !is_env_key(&p.label) {
+                    anyhow::bail!("Invalid env variable name: {}", p.label);
+                }
+                // SYMBOL 中没有单引号，用单引号包裹可以避免 $ # 等字符被 shell 解释
+                content.push_str(&format!(
+                    "# strength: {}, entropy: {} bits\n",
+                    p.strength, p.entropy_bits
+                ));
+                content.push_str(&format!("{}='{}'\n", p.label, *p.password));
+            }
+            content
+        }
+    };
+
+    Ok(Zeroizing::new(content))
+}
+
+fn is_env_key(key: &str) -> bool {
+    let mut chars = key.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => {
+            chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(length: u8) -> PasswordPolicy {
+        PasswordPolicy {
+            length,
+            bits: None,
+            uppercase: true,
+            lowercase: true,
+            number: true,
+            symbol: true,
+        }
+    }
+
+    #[test]
+    fn test_process_genpass_has_every_class() -> Result<()> {
+        let password = process_genpass(&policy(4), &mut OsRng)?;
+        let bytes = password.value.as_bytes();
+        for class in [UPPER, LOWER, NUMBER, SYMBOL] {
+            assert!(bytes.iter().any(|b| class.contains(b)));
+        }
+
+        assert!(process_genpass(&policy(3), &mut OsRng).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_process_genpass_derive() -> Result<()> {
+        let master = b"correct horse battery staple";
+        let policy = policy(20);
+        let p1 = process_genpass_derive(master, "example.com", "alice", 1, &policy)?;
+        let p2 = process_genpass_derive(master, "Example.com", "alice", 1, &policy)?;
+        let p3 = process_genpass_derive(master, "example.com", "alice", 2, &policy)?;
+        let p4 = process_genpass_derive(master, "example.com", "bob", 1, &policy)?;
+
+        assert_eq!(p1.value, p2.value);
+        assert_ne!(p1.value, p3.value);
+        assert_ne!(p1.value, p4.value);
+        assert_eq!(p1.value.len(), 20);
+        assert!(p1.value.bytes().all(|b| policy.alphabet().contains(&b)));
+        Ok(())
+    }
+
+    #[test]
+    fn test_genpass_derive_v1_vector() -> Result<()> {
+        // 固定的派生结果，字母表或派生流程变化时这里会失败
+        let master = b"correct horse battery staple";
+        let password = process_genpass_derive(master, "example.com", "alice", 1, &policy(20))?;
+        assert_eq!(password.value.as_str(), "NGK9P_!G43Uc6G3oV$r2");
+        assert_eq!(password.alphabet_size, 67);
+        Ok(())
+    }
+
+    #[test]
+    fn test_process_genpass_pronounceable() -> Result<()> {
+        let policy = policy(12);
+        let generated = process_genpass_pronounceable(&policy, &mut OsRng)?;
+        let password = generated.value.as_str();
+        assert_eq!(password.len(), 12);
+
+        let letters: Vec<u8> = password
+            .bytes()
+            .filter(u8::is_ascii_alphabetic)
+            .map(|b| b.to_ascii_lowercase())
+            .collect();
+        assert_eq!(letters.len(), 10);
+        for (i, b) in letters.iter().enumerate() {
+            let class = if i % 2 == 0 { CONSONANT } else { VOWEL };
+            assert!(class.contains(b));
+        }
+        assert_eq!(password.bytes().filter(u8::is_ascii_uppercase).count(), 1);
+
+        // 5 个音节 + 大写位置 + 数字和符号及其插入位置
+        let expected = 5.0 * (16f64 * 5.0).log2() + 5f64.log2() + 2.0 * (9f64 * 6.0).log2();
+        assert!((generated.entropy_bits - expected).abs() < 1e-9);
+        assert!(generated.entropy_bits < policy.entropy_bits());
+        assert_eq!(generated.alphabet_size, 16 + 5 + 16 + 9 + 9);
+        Ok(())
+    }
+
+    #[test]
+    fn test_process_genpass_with_bits() -> Result<()> {
+        let policy = PasswordPolicy {
+            bits: Some(128),
+            ..policy(16)
+        };
+
+        // 字母表大小为 67，log2(67) ≈ 6.07，需要 22 个字符
+        let generated = process_genpass(&policy, &mut OsRng)?;
+        assert_eq!(generated.alphabet_size, 67);
+        assert_eq!(generated.policy.length, 22);
+        assert_eq!(generated.value.len(), 22);
+        assert!(generated.entropy_bits >= 128.0);
+
+        let generated = process_genpass_pronounceable(&policy, &mut OsRng)?;
+        assert!(generated.entropy_bits >= 128.0);
+        let shorter = PasswordPolicy {
+            length: generated.policy.length - 1,
+            bits: None,
+            ..policy
+        };
+        assert!(shorter.pronounceable_entropy_bits() < 128.0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_resolve_without_classes() {
+        let policy = PasswordPolicy {
+            bits: Some(64),
+            uppercase: false,
+            lowercase: false,
+            number: false,
+            symbol: false,
+            ..policy(16)
+        };
+        let err = policy.resolve(false).unwrap_err();
+        assert!(err.to_string().contains("character class"));
+    }
+
+    #[test]
+    fn test_build_rng() -> Result<()> {
+        let policy = policy(16);
+        let p1 = process_genpass(&policy, &mut build_rng(RngSource::ChaCha, Some("01"))?)?;
+        let p2 = process_genpass(&policy, &mut build_rng(RngSource::ChaCha, Some("01"))?)?;
+        let p3 = process_genpass(&policy, &mut build_rng(RngSource::ChaCha, Some("02"))?)?;
+        assert_eq!(p1.value, p2.value);
+        assert_ne!(p1.value, p3.value);
+
+        assert!(build_rng(RngSource::ChaCha, None).is_err());
+        assert!(build_rng(RngSource::ChaCha, Some("zz")).is_err());
+        assert!(build_rng(RngSource::ChaCha, Some("")).is_err());
+        // 末尾的 0 字节也会改变随机流
+        let p4 = process_genpass(&policy, &mut build_rng(RngSource::ChaCha, Some("0100"))?)?;
+        assert_ne!(p1.value, p4.value);
+        assert!(build_rng(RngSource::Os, Some("01")).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_explain_genpass() -> Result<()> {
+        let explain = explain_genpass(&policy(16), false, RngSource::ChaCha)?;
+        assert!(explain.contains("TEST ONLY"));
+        assert!(explain.contains("alphabet size: 67"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_process_genpass_pin() -> Result<()> {
+        let pin = process_genpass_pin(6)?;
+        assert_eq!(pin.len(), 6);
+        assert!(pin.bytes().all(|b| b.is_ascii_digit()));
+        assert!(process_genpass_pin(0).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_process_recovery_codes() -> Result<()> {
+        let groups: CodeGroups = "4x4".parse()?;
+        let codes = process_recovery_codes(10, groups)?;
+        assert_eq!(codes.len(), 10);
+        for code in codes {
+            let parts: Vec<&str> = code.split('-').collect();
+            assert_eq!(parts.len(), 4);
+            assert!(parts
+                .iter()
+                .all(|p| p.len() == 4 && p.bytes().all(|b| CROCKFORD.contains(&b))));
+        }
+
+        assert!("4".parse::<CodeGroups>().is_err());
+        assert!("0x4".parse::<CodeGroups>().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_pick_unbiased() -> Result<()> {
+        // 256 % 9 = 4，252 以上的字节会被丢弃
+        let bytes = [255u8, 252, 251, 7];
+        let mut iter = bytes.iter();
+        assert_eq!(pick_unbiased(&mut iter, 9)?, 251 % 9);
+        assert_eq!(pick_unbiased(&mut iter, 9)?, 7);
+        assert!(pick_unbiased(&mut iter, 9).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_process_genpass_batch() -> Result<()> {
+        let labels = vec!["db_pass".to_string(), "redis_pass".to_string()];
+        let passwords = process_genpass_batch(3, &labels, &policy(16), false, &mut OsRng)?;
+        assert_eq!(passwords.len(), 3);
+        assert_eq!(passwords[0].label, "db_pass");
+        assert_eq!(passwords[1].label, "redis_pass");
+        assert_eq!(passwords[2].label, "password_3");
+        assert!(passwords.iter().all(|p| p.password.len() == 16));
+        Ok(())
+    }
+
+    #[test]
+    fn test_format_passwords() -> Result<()> {
+        let passwords = vec![LabeledPassword {
+            label: "db_pass".to_string(),
+            password: Zeroizing::new("a$b#c".to_string()),
+            strength: 1,
+            entropy_bits: 30.5,
+        }];
+
+        let env = format_passwords(&passwords, GenPassFormat::Env)?;
+        assert_eq!(*env, "# strength: 1, entropy: 30.5 bits\ndb_pass='a$b#c'\n");
+
+        let csv = format_passwords(&passwords, GenPassFormat::Csv)?;
+        assert_eq!(
+            *csv,
+            "label,password,strength,entropy_bits\ndb_pass,a$b#c,1,30.5\n"
+        );
+
+        let json: serde_json::Value =
+            serde_json::from_str(&format_passwords(&passwords, GenPassFormat::Json)?)?;
+        assert_eq!(json[0]["password"], "a$b#c");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_env_format_rejects_invalid_label() {
+        let passwords = vec![LabeledPassword {
+            label: "db-pass".to_string(),
+            password: Zeroizing::new("secret".to_string()),
+            strength: 0,
+            entropy_bits: 0.0,
+        }];
+        assert!(format_passwords(&passwords, GenPassFormat::Env).is_err());
+    }
 }
@@ -0,0 +1,129 @@
+use std::io::{self, Read, Write};
+
+use anyhow::Result;
+use sha2::{Digest, Sha256, Sha512};
+
+use crate::{encode, get_reader, multibase_encode, Encoding, HashAlgorithm};
+
+/// 流式计算摘要，不会把整个文件读进内存
+pub fn process_hash(input: &str, algorithm: HashAlgorithm) -> Result<Vec<u8>> {
+    let mut reader = get_reader(input)?;
+    hash_reader(&mut reader, algorithm)
+}
+
+/// 计算摘要并按需包装为 multihash，返回编码后的字符串
+/// multihash 默认使用 base58btc 的 multibase 编码，带 z 前缀
+/// sha256 时去掉 z 前缀才是 IPFS CIDv0（Qm 开头）的形式
+pub fn process_hash_encoded(
+    input: &str,
+    algorithm: HashAlgorithm,
+    multihash: bool,
+    encoding: Option<Encoding>,
+) -> Result<String> {
+    let digest = process_hash(input, algorithm)?;
+    if multihash {
+        let encoding = encoding.unwrap_or(Encoding::Base58);
+        multibase_encode(&wrap_multihash(algorithm, &digest), encoding)
+    } else {
+        encode(&digest, encoding.unwrap_or(Encoding::Hex))
+    }
+}
+
+pub fn hash_reader(reader: &mut dyn Read, algorithm: HashAlgorithm) -> Result<Vec<u8>> {
+    let digest = match algorithm {
+        HashAlgorithm::Blake3 => {
+            let mut hasher = blake3::Hasher::new();
+            io::copy(reader, &mut hasher)?;
+            hasher.finalize().as_bytes().to_vec()
+        }
+        HashAlgorithm::Sha256 => {
+            let mut hasher = Sha256::new();
+            io::copy(reader, &mut DigestWriter(&mut hasher))?;
+            hasher.finalize().to_vec()
+        }
+        HashAlgorithm::Sha512 => {
+            let mut hasher = Sha512::new();
+            io::copy(reader, &mut DigestWriter(&mut hasher))?;
+            hasher.finalize().to_vec()
+        }
+    };
+
+    Ok(digest)
+}
+
+/// multihash: varint(code) + varint(len) + digest，code 见
+/// https://github.com/multiformats/multicodec/blob/master/table.csv
+pub fn wrap_multihash(algorithm: HashAlgorithm, digest: &[u8]) -> Vec<u8> {
+    let code = match algorithm {
+        HashAlgorithm::Sha256 => 0x12,
+        HashAlgorithm::Sha512 => 0x13,
+        HashAlgorithm::Blake3 => 0x1e,
+    };
+
+    let mut buf = Vec::with_capacity(digest.len() + 4);
+    write_varint(&mut buf, code);
+    write_varint(&mut buf, digest.len() as u64);
+    buf.extend_from_slice(digest);
+    buf
+}
+
+// unsigned LEB128，每个字节低 7 位存数据，最高位表示后面还有字节
+fn write_varint(buf: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        buf.push((value as u8 & 0x7f) | 0x80);
+        value >>= 7;
+    }
+    buf.push(value as u8);
+}
+
+// sha2 的 hasher 没有实现 io::Write，包一层以便使用 io::copy
+struct DigestWriter<'a, D: Digest>(&'a mut D);
+
+impl<D: Digest> Write for DigestWriter<'_, D> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.update(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_multihash_sha256() -> Result<()> {
+        let digest = hash_reader(&mut &b"hello world"[..], HashAlgorithm::Sha256)?;
+        let multihash = wrap_multihash(HashAlgorithm::Sha256, &digest);
+        assert_eq!(&multihash[..2], &[0x12, 0x20]);
+        assert_eq!(
+            multibase_encode(&multihash, Encoding::Base58)?,
+            "zQmaozNR7DZHQK1ZcU9p7QdrshMvXqWK6gpu5rmrkPdT3L4"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_hash_blake3() -> Result<()> {
+        let digest = hash_reader(&mut &b""[..], HashAlgorithm::Blake3)?;
+        assert_eq!(
+            encode(&digest, Encoding::Hex)?,
+            "af1349b9f5f9a1a6a0404dea36dcc9499bcb25c9adc112b7cc9a93cae41f3262"
+        );
+        assert_eq!(
+            &wrap_multihash(HashAlgorithm::Blake3, &digest)[..2],
+            &[0x1e, 0x20]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_varint() {
+        let mut buf = Vec::new();
+        write_varint(&mut buf, 300);
+        assert_eq!(buf, [0xac, 0x02]);
+    }
+}
@@ -0,0 +1,298 @@
+use std::io::{self, BufRead, BufReader, Read, Write};
+
+use anyhow::Result;
+
+use crate::{get_reader, get_writer, HexdumpStyle};
+
+// 没有指定 --cols 时和 xxd 一致，默认每行 16 个字节，-p 时每行 30 个字节
+const DEFAULT_COLS: usize = 16;
+const PLAIN_COLS: usize = 30;
+// -r 时偏移量之间的空洞用 0 补齐，限制大小，防止 ffffffffffffffff: 这样的偏移写出巨大的文件
+const MAX_REVERSE_GAP: u64 = 16 * 1024 * 1024;
+
+pub fn process_hexdump(input: &str, output: &str, style: &HexdumpStyle) -> Result<()> {
+    let mut reader = get_reader(input)?;
+    let mut writer = get_writer(output)?;
+    let mut dump = HexdumpWriter::new(&mut writer, *style);
+    io::copy(&mut reader, &mut dump)?;
+    dump.finish()?;
+    writer.flush()?;
+
+    Ok(())
+}
+
+pub fn process_hexdump_reverse(input: &str, output: &str, style: &HexdumpStyle) -> Result<()> {
+    let mut reader = BufReader::new(get_reader(input)?);
+    let mut writer = get_writer(output)?;
+    if style.plain {
+        reverse_plain(&mut reader, &mut writer)?;
+    } else {
+        reverse_hexdump(&mut reader, &mut writer)?;
+    }
+    writer.flush()?;
+
+    Ok(())
+}
+
+/// 边写边输出 hexdump，凑满一行才输出，最后不足一行的部分在 finish 中输出
+pub struct HexdumpWriter<W: Write> {
+    inner: W,
+    style: HexdumpStyle,
+    offset: u64,
+    line: Vec<u8>,
+}
+
+impl<W: Write> HexdumpWriter<W> {
+    pub fn new(inner: W, style: HexdumpStyle) -> Self {
+        Self {
+            inner,
+            style,
+            offset: 0,
+            line: Vec::with_capacity(cols(&style)),
+        }
+    }
+
+    pub fn finish(mut self) -> io::Result<W> {
+        if !self.line.is_empty() {
+            self.write_line()?;
+        }
+        Ok(self.inner)
+    }
+
+    fn write_line(&mut self) -> io::Result<()> {
+        let line = format_line(self.offset, &self.line, cols(&self.style), &self.style);
+        self.inner.write_all(line.as_bytes())?;
+        self.offset += self.line.len() as u64;
+        self.line.clear();
+        Ok(())
+    }
+}
+
+impl<W: Write> Write for HexdumpWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let cols = cols(&self.style);
+        let mut rest = buf;
+        while !rest.is_empty() {
+            let n = (cols - self.line.len()).min(rest.len());
+            self.line.extend_from_slice(&rest[..n]);
+            rest = &rest[n..];
+            if self.line.len() == cols {
+                self.write_line()?;
+            }
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+fn cols(style: &HexdumpStyle) -> usize {
+    match (style.cols, style.plain) {
+        (Some(cols), _) => cols as usize,
+        (None, true) => PLAIN_COLS,
+        (None, false) => DEFAULT_COLS,
+    }
+}
+
+/// 00000010: 4865 6c6c 6f0a                           Hello.
+/// 最后一行不足 cols 时用空格补齐，保证 ASCII 列对齐
+fn format_line(offset: u64, bytes: &[u8], cols: usize, style: &HexdumpStyle) -> String {
+    let hex = |b: &u8| {
+        if style.uppercase {
+            format!("{:02X}", b)
+        } else {
+            format!("{:02x}", b)
+        }
+    };
+
+    if style.plain {
+        let mut line: String = bytes.iter().map(hex).collect();
+        line.push('\n');
+        return line;
+    }
+
+    let group = style.group as usize;
+    let mut hex_col = String::new();
+    for i in 0..cols {
+        if group > 0 && i > 0 && i % group == 0 {
+            hex_col.push(' ');
+        }
+        match bytes.get(i) {
+            Some(b) => hex_col.push_str(&hex(b)),
+            None => hex_col.push_str("  "),
+        }
+    }
+    let ascii: String = bytes
+        .iter()
+        .map(|&b| {
+            if b.is_ascii_graphic() || b == b' ' {
+                b as char
+            } else {
+                '.'
+            }
+        })
+        .collect();
+
+    format!("{:08x}: {}  {}\n", offset, hex_col, ascii)
+}
+
+/// 解析 `offset: hex  ascii` 格式，偏移量之间有空洞时补 0，和 xxd -r 一样
+/// 十六进制列和 ASCII 列之间用两个空格分隔
+pub fn reverse_hexdump(reader: &mut dyn BufRead, writer: &mut dyn Write) -> Result<()> {
+    let mut written: u64 = 0;
+    for (n, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let (offset, rest) = line
+            .split_once(':')
+            .ok_or_else(|| anyhow::anyhow!("Line {}: missing offset", n + 1))?;
+        let offset = u64::from_str_radix(offset.trim(), 16)
+            .map_err(|_| anyhow::anyhow!("Line {}: invalid offset {:?}", n + 1, offset))?;
+        let rest = rest.strip_prefix(' ').unwrap_or(rest);
+        let hex_col = rest.split("  ").next().unwrap_or_default();
+        let bytes = parse_hex(hex_col).map_err(|e| anyhow::anyhow!("Line {}: {}", n + 1, e))?;
+
+        if offset < written {
+            anyhow::bail!("Line {}: offset {:#x} goes backwards", n + 1, offset);
+        }
+        if offset - written > MAX_REVERSE_GAP {
+            anyhow::bail!(
+                "Line {}: offset {:#x} skips more than {} bytes",
+                n + 1,
+                offset,
+                MAX_REVERSE_GAP
+            );
+        }
+        io::copy(&mut io::repeat(0).take(offset - written), writer)?;
+        writer.write_all(&bytes)?;
+        written = offset + bytes.len() as u64;
+    }
+
+    Ok(())
+}
+
+/// xxd -r -p：忽略所有空白字符，只有连续的十六进制
+pub fn reverse_plain(reader: &mut dyn BufRead, writer: &mut dyn Write) -> Result<()> {
+    for line in reader.lines() {
+        writer.write_all(&parse_hex(&line?)?)?;
+    }
+
+    Ok(())
+}
+
+fn parse_hex(hex: &str) -> Result<Vec<u8>> {
+    let digits: Vec<u8> = hex.bytes().filter(|b| !b.is_ascii_whitespace()).collect();
+    if !digits.len().is_multiple_of(2) {
+        anyhow::bail!("Odd number of hex digits");
+    }
+    digits
+        .chunks(2)
+        .map(|pair| {
+            let pair = std::str::from_utf8(pair)?;
+            u8::from_str_radix(pair, 16).map_err(|_| anyhow::anyhow!("Invalid hex {:?}", pair))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dump(data: &[u8], style: HexdumpStyle) -> Result<String> {
+        let mut dump = HexdumpWriter::new(Vec::new(), style);
+        dump.write_all(data)?;
+        Ok(String::from_utf8(dump.finish()?)?)
+    }
+
+    #[test]
+    fn test_hexdump_matches_xxd() -> Result<()> {
+        let output = dump(b"Hello, world! rcli hexdump\n", HexdumpStyle::default())?;
+        assert_eq!(
+            output,
+            "00000000: 4865 6c6c 6f2c 2077 6f72 6c64 2120 7263  Hello, world! rc\n\
+             00000010: 6c69 2068 6578 6475 6d70 0a              li hexdump.\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_hexdump_group_and_cols() -> Result<()> {
+        let style = HexdumpStyle {
+            cols: Some(8),
+            group: 4,
+            uppercase: true,
+            ..Default::default()
+        };
+        let output = dump(&[0xde, 0xad, 0xbe, 0xef, 0x00], style)?;
+        assert_eq!(output, "00000000: DEADBEEF 00        .....\n");
+        Ok(())
+    }
+
+    #[test]
+    fn test_hexdump_plain_cols() -> Result<()> {
+        let data: Vec<u8> = (0..40u8).collect();
+        let plain = HexdumpStyle {
+            plain: true,
+            ..Default::default()
+        };
+        let output = dump(&data, plain)?;
+        assert_eq!(output.lines().map(str::len).collect::<Vec<_>>(), [60, 20]);
+
+        // 和 xxd -p -c 4 一样，--cols 在 plain 模式下也生效
+        let output = dump(
+            &data[..6],
+            HexdumpStyle {
+                cols: Some(4),
+                ..plain
+            },
+        )?;
+        assert_eq!(output, "00010203\n0405\n");
+        Ok(())
+    }
+
+    #[test]
+    fn test_hexdump_reverse_roundtrip() -> Result<()> {
+        let data: Vec<u8> = (0..=255u8).chain(b"  tail".iter().copied()).collect();
+        for style in [
+            HexdumpStyle::default(),
+            HexdumpStyle {
+                cols: Some(7),
+                group: 0,
+                ..Default::default()
+            },
+        ] {
+            let output = dump(&data, style)?;
+            let mut reversed = Vec::new();
+            reverse_hexdump(&mut output.as_bytes(), &mut reversed)?;
+            assert_eq!(reversed, data);
+        }
+
+        let plain = dump(
+            &data,
+            HexdumpStyle {
+                plain: true,
+                ..Default::default()
+            },
+        )?;
+        let mut reversed = Vec::new();
+        reverse_plain(&mut plain.as_bytes(), &mut reversed)?;
+        assert_eq!(reversed, data);
+        Ok(())
+    }
+
+    #[test]
+    fn test_hexdump_reverse_fills_gap() -> Result<()> {
+        let mut reversed = Vec::new();
+        reverse_hexdump(&mut &b"00000004: 4142  AB\n"[..], &mut reversed)?;
+        assert_eq!(reversed, b"\0\0\0\0AB");
+        assert!(reverse_hexdump(&mut &b"0000: 4\n"[..], &mut Vec::new()).is_err());
+        // 不受信任的偏移量不能导致写出巨大的空洞
+        let huge = b"00000000: 4142  AB\nffffffffffffffff: 43  C\n";
+        assert!(reverse_hexdump(&mut &huge[..], &mut Vec::new()).is_err());
+        Ok(())
+    }
+}
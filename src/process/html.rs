@@ -0,0 +1,70 @@
+use std::io::Write;
+
+use anyhow::Result;
+
+use crate::{get_writer, read_text};
+
+pub fn process_html_escape(input: &str, output: &str) -> Result<()> {
+    let data = read_text(input)?;
+    let mut writer = get_writer(output)?;
+    writeln!(writer, "{}", escape_html(&data))?;
+    writer.flush()?;
+
+    Ok(())
+}
+
+pub fn process_html_unescape(input: &str, output: &str) -> Result<()> {
+    let data = read_text(input)?;
+    let mut writer = get_writer(output)?;
+    writeln!(writer, "{}", unescape_html(&data))?;
+    writer.flush()?;
+
+    Ok(())
+}
+
+/// 转义 & < > " '，结果可以放在元素内容和带引号的属性值中
+pub fn escape_html(data: &str) -> String {
+    let mut ret = String::with_capacity(data.len());
+    for c in data.chars() {
+        match c {
+            '&' => ret.push_str("&amp;"),
+            '<' => ret.push_str("&lt;"),
+            '>' => ret.push_str("&gt;"),
+            '"' => ret.push_str("&quot;"),
+            '\'' => ret.push_str("&#39;"),
+            _ => ret.push(c),
+        }
+    }
+    ret
+}
+
+/// 支持 HTML5 的全部命名实体，以及 &#39; / &#x27; 这样的数字实体
+pub fn unescape_html(data: &str) -> String {
+    html_escape::decode_html_entities(data).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_html_escape_roundtrip() {
+        let data = r#"<a href="x?a=1&b='2'">Tom & Jerry</a>"#;
+        let escaped = escape_html(data);
+        assert_eq!(
+            escaped,
+            "&lt;a href=&quot;x?a=1&amp;b=&#39;2&#39;&quot;&gt;Tom &amp; Jerry&lt;/a&gt;"
+        );
+        assert_eq!(unescape_html(&escaped), data);
+    }
+
+    #[test]
+    fn test_html_unescape_entities() {
+        assert_eq!(
+            unescape_html("&copy; &#x1F600; &#8364; &nbsp;|"),
+            "© 😀 € \u{a0}|"
+        );
+        // 不认识的实体和缺少分号的实体原样保留
+        assert_eq!(unescape_html("&unknown; & &amp"), "&unknown; & &amp");
+    }
+}
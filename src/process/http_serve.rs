@@ -5,12 +5,12 @@ use std::sync::Arc;
 use anyhow::Result;
 use axum::extract::{Path, State};
 use axum::http::StatusCode;
-use axum::routing::get;
 use axum::Router;
 use tokio::net::TcpListener;
 use tower_http::services::ServeDir;
 use tracing::{error, info};
 
+#[allow(dead_code)]
 #[derive(Debug)]
 struct HttpServeState {
     path: PathBuf,
@@ -43,6 +43,7 @@ pub async fn process_http_server(path: PathBuf, port: u16) -> Result<()> {
 }
 
 // 使用 pattern match
+#[allow(dead_code)]
 async fn file_handler(
     State(state): State<Arc<HttpServeState>>,
     Path(path): Path<String>,
@@ -51,10 +52,10 @@ async fn file_handler(
     info!("Reading file {:?}", p);
 
     if !p.exists() {
-        return (
+        (
             StatusCode::NOT_FOUND,
             format!("File {} not found", p.display()),
-        );
+        )
     } else {
         match tokio::fs::read_to_string(p).await {
             Ok(content) => {
@@ -70,6 +71,7 @@ async fn file_handler(
 }
 
 // 不使用 pattern match ，写法比较啰嗦
+#[allow(dead_code)]
 async fn hello(state: State<Arc<HttpServeState>>) -> String {
     format!("hello {:?}", state.0)
 }
@@ -77,7 +79,7 @@ async fn hello(state: State<Arc<HttpServeState>>) -> String {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::{path::Path, path::PathBuf, sync::Arc};
+    use std::{path::PathBuf, sync::Arc};
 
     #[tokio::test]
     async fn test_file_handler() {
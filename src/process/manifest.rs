@@ -0,0 +1,350 @@
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+use std::fs::{self, File};
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+use anyhow::Result;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use rayon::prelude::*;
+use walkdir::WalkDir;
+
+use super::text::{Ed25519Singer, Ed25519Verifier, KeyLoader, TextError, TextSign, TextVerify};
+
+/// manifest 的格式，按路径排序，每个文件一行：
+///
+/// rcli-manifest v1 blake3
+/// <blake3 hex>  <size>  <path>
+///
+/// 路径相对于目录，使用 / 分隔；签名和 text sign 一样是 Ed25519ph，写入 <manifest>.sig
+const MANIFEST_HEADER: &str = "rcli-manifest v1 blake3";
+const DEFAULT_MANIFEST: &str = "MANIFEST.b3";
+const SEPARATOR: &str = "  ";
+
+type FileDigests = BTreeMap<String, FileDigest>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileDigest {
+    pub size: u64,
+    pub hash: blake3::Hash,
+}
+
+/// verify-manifest 的结果，三个列表都为空时说明目录和 manifest 一致
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct ManifestReport {
+    pub checked: usize,
+    pub missing: Vec<String>,
+    pub extra: Vec<String>,
+    pub modified: Vec<String>,
+}
+
+/// 计算目录下所有文件的 blake3，写入 manifest 并用 ed25519 私钥签名
+/// 返回 manifest 的路径和文件数量
+pub fn process_text_sign_manifest(
+    dir: &Path,
+    key: &str,
+    manifest: Option<&Path>,
+) -> Result<(PathBuf, usize)> {
+    // 先加载私钥，加密的私钥在哈希之前提示输入口令
+    let signer = Ed25519Singer::load(key)?;
+    let manifest = manifest_path(dir, manifest);
+    let digests = hash_dir(dir, &manifest)?;
+    let content = encode_manifest(&digests);
+    let sig = signer.sign(&mut content.as_bytes())?;
+
+    fs::write(&manifest, &content)?;
+    fs::write(
+        sig_path(&manifest),
+        format!("{}\n", URL_SAFE_NO_PAD.encode(sig)),
+    )?;
+
+    Ok((manifest, digests.len()))
+}
+
+/// 先验证 manifest 的签名，再和目录下的文件逐个比较
+pub fn process_text_verify_manifest(
+    dir: &Path,
+    key: &str,
+    manifest: Option<&Path>,
+) -> Result<ManifestReport> {
+    let manifest = manifest_path(dir, manifest);
+    let content = fs::read_to_string(&manifest)?;
+    let sig = fs::read_to_string(sig_path(&manifest))?;
+    let sig = URL_SAFE_NO_PAD
+        .decode(sig.trim())
+        .map_err(|e| TextError::MalformedSignature(e.to_string()))?;
+    let verifier = Ed25519Verifier::load(key)?;
+    if !verifier.verify(content.as_bytes(), &sig)? {
+        return Err(TextError::SignatureMismatch.into());
+    }
+
+    let expected = decode_manifest(&content)?;
+    let actual = hash_dir(dir, &manifest)?;
+
+    Ok(compare(&expected, &actual))
+}
+
+impl ManifestReport {
+    pub fn is_clean(&self) -> bool {
+        self.missing.is_empty() && self.extra.is_empty() && self.modified.is_empty()
+    }
+}
+
+impl Display for ManifestReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for path in &self.missing {
+            writeln!(f, "missing: {}", path)?;
+        }
+        for path in &self.extra {
+            writeln!(f, "extra: {}", path)?;
+        }
+        for path in &self.modified {
+            writeln!(f, "modified: {}", path)?;
+        }
+        write!(
+            f,
+            "{} files checked, {} missing, {} extra, {} modified",
+            self.checked,
+            self.missing.len(),
+            self.extra.len(),
+            self.modified.len()
+        )
+    }
+}
+
+fn compare(expected: &FileDigests, actual: &FileDigests) -> ManifestReport {
+    let mut report = ManifestReport {
+        checked: expected.len(),
+        ..Default::default()
+    };
+    // BTreeMap 按路径排序，输出的顺序和 manifest 一致
+    for (path, digest) in expected {
+        match actual.get(path) {
+            None => report.missing.push(path.clone()),
+            Some(current) if current != digest => report.modified.push(path.clone()),
+            Some(_) => {}
+        }
+    }
+    report.extra = actual
+        .keys()
+        .filter(|path| !expected.contains_key(*path))
+        .cloned()
+        .collect();
+
+    report
+}
+
+// 默认放在目录下，哈希时跳过 manifest 和签名文件本身
+fn manifest_path(dir: &Path, manifest: Option<&Path>) -> PathBuf {
+    manifest.map_or_else(|| dir.join(DEFAULT_MANIFEST), Path::to_path_buf)
+}
+
+fn sig_path(manifest: &Path) -> PathBuf {
+    let mut path = manifest.as_os_str().to_owned();
+    path.push(".sig");
+    PathBuf::from(path)
+}
+
+// 先遍历得到文件列表，再用 rayon 并行计算哈希
+fn hash_dir(dir: &Path, manifest: &Path) -> Result<FileDigests> {
+    let skip = [manifest.to_path_buf(), sig_path(manifest)]
+        .iter()
+        .filter_map(|path| path.canonicalize().ok())
+        .collect::<Vec<_>>();
+
+    let mut files = Vec::new();
+    // 不跟随符号链接，避免把目录外的文件签进去；FIFO、设备文件读取时可能一直阻塞
+    // 这些都不是普通文件，直接报错，不静默跳过
+    for entry in WalkDir::new(dir) {
+        let entry = entry?;
+        if entry.file_type().is_dir() {
+            continue;
+        }
+        if !entry.file_type().is_file() {
+            anyhow::bail!("{} is not a regular file", entry.path().display());
+        }
+        if skip.contains(&entry.path().canonicalize()?) {
+            continue;
+        }
+        let relative = relative_path(entry.path().strip_prefix(dir)?)?;
+        files.push((relative, entry.into_path()));
+    }
+
+    files
+        .into_par_iter()
+        .map(|(relative, path)| Ok((relative, hash_file(&path)?)))
+        .collect()
+}
+
+fn hash_file(path: &Path) -> Result<FileDigest> {
+    let mut hasher = blake3::Hasher::new();
+    let size = io::copy(&mut File::open(path)?, &mut hasher)?;
+    Ok(FileDigest {
+        size,
+        hash: hasher.finalize(),
+    })
+}
+
+// 不同平台的 manifest 保持一致，统一用 / 分隔
+fn relative_path(path: &Path) -> Result<String> {
+    let mut parts = Vec::new();
+    for component in path.components() {
+        let Component::Normal(part) = component else {
+            anyhow::bail!("Unexpected path component in {}", path.display());
+        };
+        let part = part
+            .to_str()
+            .ok_or_else(|| anyhow::anyhow!("{} is not valid UTF-8", path.display()))?;
+        parts.push(part);
+    }
+    let relative = parts.join("/");
+    if relative.contains(['\n', '\r']) {
+        anyhow::bail!("File name contains a line break: {:?}", relative);
+    }
+
+    Ok(relative)
+}
+
+fn encode_manifest(digests: &FileDigests) -> String {
+    let mut content = format!("{}\n", MANIFEST_HEADER);
+    for (path, digest) in digests {
+        content.push_str(&format!(
+            "{}{}{}{}{}\n",
+            digest.hash.to_hex(),
+            SEPARATOR,
+            digest.size,
+            SEPARATOR,
+            path
+        ));
+    }
+    content
+}
+
+fn decode_manifest(content: &str) -> Result<FileDigests> {
+    let mut lines = content.lines();
+    if lines.next() != Some(MANIFEST_HEADER) {
+        anyhow::bail!("Not an rcli manifest, expected \"{}\"", MANIFEST_HEADER);
+    }
+
+    let mut digests = FileDigests::new();
+    for (index, line) in lines.enumerate() {
+        // 第一行是 header，行号从 2 开始
+        let line_no = index + 2;
+        // 路径放在最后，可以包含空格
+        let mut fields = line.splitn(3, SEPARATOR);
+        let (Some(hash), Some(size), Some(path)) = (fields.next(), fields.next(), fields.next())
+        else {
+            anyhow::bail!("Malformed manifest line {}", line_no);
+        };
+        let digest = FileDigest {
+            size: size
+                .parse()
+                .map_err(|e| anyhow::anyhow!("Invalid size on manifest line {}: {}", line_no, e))?,
+            hash: blake3::Hash::from_hex(hash)
+                .map_err(|e| anyhow::anyhow!("Invalid hash on manifest line {}: {}", line_no, e))?,
+        };
+        if digests.insert(path.to_string(), digest).is_some() {
+            anyhow::bail!("Duplicate path in manifest: {}", path);
+        }
+    }
+
+    Ok(digests)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_manifest_roundtrip() -> Result<()> {
+        let tmp = tempfile::tempdir()?;
+        let dir = tmp.path();
+        fs::create_dir_all(dir.join("sub dir"))?;
+        fs::write(dir.join("a.txt"), "hello")?;
+        fs::write(dir.join("sub dir/b  c.bin"), [0u8; 4096])?;
+
+        let digests = hash_dir(dir, &dir.join(DEFAULT_MANIFEST))?;
+        let content = encode_manifest(&digests);
+        assert!(content.starts_with(&format!(
+            "{}\n{}  5  a.txt\n",
+            MANIFEST_HEADER,
+            blake3::hash(b"hello").to_hex()
+        )));
+        assert!(content.ends_with("  4096  sub dir/b  c.bin\n"));
+        assert_eq!(decode_manifest(&content)?, digests);
+        Ok(())
+    }
+
+    #[test]
+    fn test_sign_and_verify_manifest() -> Result<()> {
+        let tmp = tempfile::tempdir()?;
+        let dir = tmp.path();
+        fs::create_dir_all(dir.join("nested"))?;
+        fs::write(dir.join("keep.txt"), "keep")?;
+        fs::write(dir.join("change.txt"), "before")?;
+        fs::write(dir.join("nested/remove.txt"), "remove")?;
+
+        let (manifest, count) = process_text_sign_manifest(dir, "fixtures/ed25519.sk", None)?;
+        assert_eq!(manifest, dir.join(DEFAULT_MANIFEST));
+        assert_eq!(count, 3);
+        let report = process_text_verify_manifest(dir, "fixtures/ed25519.pk", None)?;
+        assert!(report.is_clean());
+        assert_eq!(report.checked, 3);
+
+        fs::write(dir.join("change.txt"), "after!")?;
+        fs::remove_file(dir.join("nested/remove.txt"))?;
+        fs::write(dir.join("nested/new.txt"), "new")?;
+        let report = process_text_verify_manifest(dir, "fixtures/ed25519.pk", None)?;
+        assert_eq!(
+            report,
+            ManifestReport {
+                checked: 3,
+                missing: vec!["nested/remove.txt".into()],
+                extra: vec!["nested/new.txt".into()],
+                modified: vec!["change.txt".into()],
+            }
+        );
+
+        // 修改 manifest 后签名验证失败
+        let content = fs::read_to_string(&manifest)?;
+        fs::write(&manifest, content.replace("keep.txt", "kept.txt"))?;
+        let err = process_text_verify_manifest(dir, "fixtures/ed25519.pk", None).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<TextError>(),
+            Some(TextError::SignatureMismatch)
+        ));
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_symlinks_are_rejected() -> Result<()> {
+        let tmp = tempfile::tempdir()?;
+        let dir = tmp.path().join("signed");
+        fs::create_dir_all(&dir)?;
+        fs::write(dir.join("a.txt"), "hello")?;
+        fs::write(tmp.path().join("outside.txt"), "outside")?;
+        // 指向目录外的链接不能被签进 manifest
+        std::os::unix::fs::symlink(tmp.path().join("outside.txt"), dir.join("link.txt"))?;
+
+        let err = hash_dir(&dir, &dir.join(DEFAULT_MANIFEST)).unwrap_err();
+        assert!(err.to_string().contains("link.txt is not a regular file"));
+
+        fs::remove_file(dir.join("link.txt"))?;
+        std::os::unix::fs::symlink(tmp.path(), dir.join("parent"))?;
+        assert!(hash_dir(&dir, &dir.join(DEFAULT_MANIFEST)).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_manifest_errors() {
+        assert!(decode_manifest("sha256sums\n").is_err());
+        let hash = blake3::hash(b"").to_hex();
+        let line = format!("{}  0  a\n", hash);
+        let duplicate = format!("{}\n{}{}", MANIFEST_HEADER, line, line);
+        assert!(decode_manifest(&duplicate).is_err());
+        assert!(decode_manifest(&format!("{}\n{}  x  a\n", MANIFEST_HEADER, hash)).is_err());
+        assert!(decode_manifest(&format!("{}\nnothex  0  a\n", MANIFEST_HEADER)).is_err());
+    }
+}
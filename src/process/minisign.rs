@@ -0,0 +1,507 @@
+use std::fmt::{Display, Formatter};
+use std::fs;
+use std::io::{self, Read};
+use std::path::Path;
+
+use anyhow::Result;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use blake2::digest::consts::U32;
+use blake2::{Blake2b, Blake2b512, Digest};
+use ed25519_dalek::{Signature, Signer, SigningKey, VerifyingKey};
+use rand::rngs::OsRng;
+use rand::RngCore;
+use zeroize::Zeroizing;
+
+use super::key_format::{
+    detect_key_format, parse_ed25519_signing_key, parse_ed25519_verifying_key,
+};
+use super::key_protect::read_key_file;
+use super::text::TextError;
+use crate::{get_reader, KeyFormat, TextSignFormat};
+
+/// minisign 的文件格式，每个文件都是 "untrusted comment: ..." 加一行 base64：
+///
+/// 公钥：Ed | key id | 公钥
+/// 私钥：Ed | kdf | B2 | scrypt salt | opslimit | memlimit | key id | 私钥 | 校验和，kdf 为 Sc 时后三项经过 scrypt 加密
+/// 签名：ED | key id | Ed25519(BLAKE2b-512(文件))，之后是 trusted comment 和对 签名 || trusted comment 的签名
+const UNTRUSTED_PREFIX: &str = "untrusted comment: ";
+const TRUSTED_PREFIX: &str = "trusted comment: ";
+const SIG_ALG: [u8; 2] = *b"Ed";
+// 对 BLAKE2b-512 后的数据签名，minisign 0.8 之后的默认值
+const SIG_ALG_PREHASHED: [u8; 2] = *b"ED";
+const KDF_SCRYPT: [u8; 2] = *b"Sc";
+const KDF_NONE: [u8; 2] = [0, 0];
+const CHK_ALG: [u8; 2] = *b"B2";
+const KEY_ID_LEN: usize = 8;
+const SECRET_KEY_LEN: usize = 158;
+const PUBLIC_KEY_LEN: usize = 42;
+const SIGNATURE_LEN: usize = 74;
+// key id | 私钥 | 校验和，加密时和 scrypt 的输出异或
+const KEYNUM_SK_LEN: usize = KEY_ID_LEN + 64 + 32;
+// 和 minisign 一致，使用 libsodium 的 SENSITIVE 参数，对应 scrypt N = 2^20, r = 8, p = 1
+const OPSLIMIT: u64 = 1 << 25;
+const MEMLIMIT: u64 = 1 << 30;
+// 默认参数每次 scrypt 需要 1 GiB 内存，测试中使用 N = 2^10
+// 默认参数由 test_scrypt_params_match_minisign 检查
+#[cfg(not(test))]
+const LOCK_LIMITS: (u64, u64) = (OPSLIMIT, MEMLIMIT);
+#[cfg(test)]
+const LOCK_LIMITS: (u64, u64) = (1 << 15, 1 << 25);
+// 读取别人的私钥时的上限，避免构造的文件让 scrypt 跑很久或者占用大量内存
+const MEMLIMIT_MAX: u64 = 1 << 30;
+const N_LOG2_MAX: u8 = 20;
+const P_MAX: u32 = 16;
+
+type KeyId = [u8; KEY_ID_LEN];
+
+/// .minisig 文件
+#[derive(Debug)]
+pub struct MinisignSignature {
+    pub untrusted_comment: String,
+    pub prehashed: bool,
+    pub key_id: KeyId,
+    pub signature: Signature,
+    pub trusted_comment: String,
+    pub global_signature: Signature,
+}
+
+/// 用 ed25519 私钥生成 minisign 格式的签名文件
+/// 私钥可以是 minisign 的私钥文件，也可以是 text generate 生成的任意格式
+pub fn process_text_sign_detached(
+    input: &str,
+    key: &str,
+    format: TextSignFormat,
+    sig_file: &str,
+    trusted_comment: Option<&str>,
+) -> Result<()> {
+    check_format(format)?;
+    let (key_id, sk) = load_signer(key)?;
+    let mut reader = get_reader(input)?;
+    let signature = sk.sign(&prehash(&mut reader)?);
+
+    // 和 minisign 默认的 trusted comment 格式一致
+    let trusted_comment = match trusted_comment {
+        Some(comment) => comment.to_string(),
+        None => {
+            let timestamp = chrono::Utc::now().timestamp();
+            match Path::new(input).file_name() {
+                Some(name) if input != "-" => format!(
+                    "timestamp:{}\tfile:{}\thashed",
+                    timestamp,
+                    name.to_string_lossy()
+                ),
+                _ => format!("timestamp:{}\thashed", timestamp),
+            }
+        }
+    };
+    if trusted_comment.contains('\n') {
+        anyhow::bail!("Trusted comment must be a single line");
+    }
+    let global_signature = sk.sign(&global_message(&signature, &trusted_comment));
+
+    let sig = MinisignSignature {
+        untrusted_comment: "signature from rcli secret key".to_string(),
+        prehashed: true,
+        key_id,
+        signature,
+        trusted_comment,
+        global_signature,
+    };
+    fs::write(sig_file, sig.to_string())?;
+
+    Ok(())
+}
+
+/// 验证 minisign 格式的签名文件，成功时返回 trusted comment
+pub fn process_text_verify_detached(
+    input: &str,
+    key: &str,
+    format: TextSignFormat,
+    sig_file: &str,
+) -> Result<String> {
+    check_format(format)?;
+    let sig = MinisignSignature::parse(&fs::read_to_string(sig_file)?)?;
+    let (key_id, pk) = load_verifier(key)?;
+    if sig.key_id != key_id {
+        anyhow::bail!(
+            "Signature was made with key {}, but the public key is {}",
+            key_id_hex(&sig.key_id),
+            key_id_hex(&key_id)
+        );
+    }
+
+    let mut reader = get_reader(input)?;
+    let verified = if sig.prehashed {
+        pk.verify_strict(&prehash(&mut reader)?, &sig.signature)
+    } else {
+        // 旧版本 minisign 的 Ed 签名直接对文件内容签名，只能全部读入内存
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data)?;
+        pk.verify_strict(&data, &sig.signature)
+    };
+    if verified.is_err() {
+        return Err(TextError::SignatureMismatch.into());
+    }
+    // trusted comment 被修改过
+    let message = global_message(&sig.signature, &sig.trusted_comment);
+    if pk.verify_strict(&message, &sig.global_signature).is_err() {
+        return Err(TextError::SignatureMismatch.into());
+    }
+
+    Ok(sig.trusted_comment)
+}
+
+impl MinisignSignature {
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut lines = text.lines().map(|line| line.trim_end_matches('\r'));
+        let (untrusted_comment, sig) = read_box(&mut lines, SIGNATURE_LEN)?;
+        let prehashed = match sig[..2].try_into()? {
+            SIG_ALG_PREHASHED => true,
+            SIG_ALG => false,
+            _ => anyhow::bail!("Unsupported minisign signature algorithm"),
+        };
+        let trusted_comment = lines
+            .next()
+            .and_then(|line| line.strip_prefix(TRUSTED_PREFIX))
+            .ok_or_else(|| anyhow::anyhow!("Signature file has no trusted comment"))?;
+        let global_signature = lines
+            .next()
+            .ok_or_else(|| anyhow::anyhow!("Signature file has no global signature"))?;
+        let global_signature = STANDARD
+            .decode(global_signature.trim())
+            .map_err(|e| TextError::MalformedSignature(e.to_string()))?;
+
+        Ok(Self {
+            untrusted_comment: untrusted_comment.to_string(),
+            prehashed,
+            key_id: sig[2..10].try_into()?,
+            signature: Signature::from_slice(&sig[10..])?,
+            trusted_comment: trusted_comment.to_string(),
+            global_signature: Signature::from_slice(&global_signature)
+                .map_err(|e| TextError::MalformedSignature(e.to_string()))?,
+        })
+    }
+}
+
+impl Display for MinisignSignature {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let alg = if self.prehashed {
+            SIG_ALG_PREHASHED
+        } else {
+            SIG_ALG
+        };
+        let sig = [&alg[..], &self.key_id, &self.signature.to_bytes()].concat();
+        writeln!(f, "{}{}", UNTRUSTED_PREFIX, self.untrusted_comment)?;
+        writeln!(f, "{}", STANDARD.encode(sig))?;
+        writeln!(f, "{}{}", TRUSTED_PREFIX, self.trusted_comment)?;
+        writeln!(f, "{}", STANDARD.encode(self.global_signature.to_bytes()))
+    }
+}
+
+pub(crate) fn is_minisign_secret_key(data: &[u8]) -> bool {
+    decode_key_box(data, SECRET_KEY_LEN).is_ok()
+}
+
+pub(crate) fn is_encrypted_minisign_key(data: &[u8]) -> bool {
+    decode_key_box(data, SECRET_KEY_LEN).is_ok_and(|(_, key)| key[2..4] == KDF_SCRYPT)
+}
+
+/// 生成 minisign 格式的密钥对，key id 和 minisign 一样随机生成，私钥不加密
+pub(crate) fn encode_minisign_keypair(sk: &SigningKey) -> (Zeroizing<Vec<u8>>, Vec<u8>) {
+    let mut key_id = [0u8; KEY_ID_LEN];
+    OsRng.fill_bytes(&mut key_id);
+    let id = key_id_hex(&key_id);
+
+    let mut secret = Zeroizing::new(Vec::with_capacity(SECRET_KEY_LEN));
+    secret.extend_from_slice(&SIG_ALG);
+    secret.extend_from_slice(&KDF_NONE);
+    secret.extend_from_slice(&CHK_ALG);
+    // 不加密时 salt 和 KDF 参数都不使用
+    secret.extend_from_slice(&[0u8; 32 + 8 + 8]);
+    secret.extend_from_slice(&key_id);
+    secret.extend_from_slice(&sk.to_keypair_bytes());
+    secret.extend_from_slice(&checksum(&key_id, &sk.to_keypair_bytes()));
+
+    let public = [&SIG_ALG[..], &key_id, sk.verifying_key().as_bytes()].concat();
+    let secret = encode_key_box(&format!("rcli secret key {}", id), &secret);
+    let public = encode_key_box(&format!("minisign public key {}", id), &public);
+
+    (Zeroizing::new(secret.into_bytes()), public.into_bytes())
+}
+
+/// 读取未加密的 minisign 私钥
+pub(crate) fn decode_minisign_secret_key(data: &[u8]) -> Result<(KeyId, SigningKey)> {
+    let (_, key) = decode_key_box(data, SECRET_KEY_LEN)?;
+    if key[..2] != SIG_ALG || key[4..6] != CHK_ALG {
+        anyhow::bail!("Unsupported minisign secret key algorithm");
+    }
+    match key[2..4].try_into()? {
+        KDF_NONE => {}
+        KDF_SCRYPT => anyhow::bail!("minisign secret key is encrypted"),
+        _ => anyhow::bail!("Unsupported minisign key derivation algorithm"),
+    }
+    let keynum_sk = &key[SECRET_KEY_LEN - KEYNUM_SK_LEN..];
+    let key_id: KeyId = keynum_sk[..KEY_ID_LEN].try_into()?;
+    let keypair: &[u8; 64] = keynum_sk[KEY_ID_LEN..KEY_ID_LEN + 64].try_into()?;
+    if checksum(&key_id, keypair) != keynum_sk[KEY_ID_LEN + 64..] {
+        anyhow::bail!("minisign secret key checksum mismatch");
+    }
+    let sk = SigningKey::from_keypair_bytes(keypair).map_err(|e| TextError::InvalidKey {
+        kind: "minisign secret",
+        reason: e.to_string(),
+    })?;
+
+    Ok((key_id, sk))
+}
+
+/// 读取 minisign 公钥，传入私钥时从私钥推导
+pub(crate) fn decode_minisign_public_key(data: &[u8]) -> Result<(KeyId, VerifyingKey)> {
+    if is_minisign_secret_key(data) {
+        let (key_id, sk) = decode_minisign_secret_key(data)?;
+        return Ok((key_id, sk.verifying_key()));
+    }
+    let (_, key) = decode_key_box(data, PUBLIC_KEY_LEN)?;
+    if key[..2] != SIG_ALG {
+        anyhow::bail!("Unsupported minisign public key algorithm");
+    }
+    let pk =
+        VerifyingKey::from_bytes(key[10..].try_into()?).map_err(|e| TextError::InvalidKey {
+            kind: "minisign public",
+            reason: e.to_string(),
+        })?;
+
+    Ok((key[2..10].try_into()?, pk))
+}
+
+/// 用口令加密 minisign 私钥，minisign 可以直接读取
+pub(crate) fn lock_minisign_secret_key(data: &[u8], passphrase: &[u8]) -> Result<Vec<u8>> {
+    let (comment, mut key) = decode_key_box(data, SECRET_KEY_LEN)?;
+    // 先检查格式和校验和
+    decode_minisign_secret_key(data)?;
+    key[2..4].copy_from_slice(&KDF_SCRYPT);
+    OsRng.fill_bytes(&mut key[6..38]);
+    key[38..46].copy_from_slice(&LOCK_LIMITS.0.to_le_bytes());
+    key[46..54].copy_from_slice(&LOCK_LIMITS.1.to_le_bytes());
+    xor_keynum_sk(&mut key, passphrase)?;
+
+    Ok(encode_key_box(comment, &key).into_bytes())
+}
+
+pub(crate) fn unlock_minisign_secret_key(
+    data: &[u8],
+    passphrase: &[u8],
+) -> Result<Zeroizing<Vec<u8>>> {
+    let (comment, mut key) = decode_key_box(data, SECRET_KEY_LEN)?;
+    xor_keynum_sk(&mut key, passphrase)?;
+    key[2..4].copy_from_slice(&KDF_NONE);
+    let unlocked = Zeroizing::new(encode_key_box(comment, &key).into_bytes());
+    // 校验和不对说明口令错误
+    decode_minisign_secret_key(&unlocked)
+        .map_err(|_| anyhow::anyhow!("Incorrect passphrase or corrupted key file"))?;
+
+    Ok(unlocked)
+}
+
+/// minisign 显示的 key id，按小端序的 u64 输出
+pub(crate) fn key_id_hex(key_id: &KeyId) -> String {
+    format!("{:016X}", u64::from_le_bytes(*key_id))
+}
+
+fn check_format(format: TextSignFormat) -> Result<()> {
+    if !matches!(format, TextSignFormat::Ed25519) {
+        anyhow::bail!("Signature files only support ed25519 keys, not {}", format);
+    }
+    Ok(())
+}
+
+// 其他格式的密钥没有 key id，用公钥的 blake3 哈希的前 8 字节
+fn derive_key_id(pk: &VerifyingKey) -> KeyId {
+    let hash = blake3::hash(pk.as_bytes());
+    hash.as_bytes()[..KEY_ID_LEN].try_into().unwrap()
+}
+
+fn load_signer(key: &str) -> Result<(KeyId, SigningKey)> {
+    let data = read_key_file(key)?;
+    if detect_key_format(&data) == KeyFormat::Minisign {
+        return decode_minisign_secret_key(&data);
+    }
+    let sk = parse_ed25519_signing_key(&data)?;
+    Ok((derive_key_id(&sk.verifying_key()), sk))
+}
+
+fn load_verifier(key: &str) -> Result<(KeyId, VerifyingKey)> {
+    let data = read_key_file(key)?;
+    if detect_key_format(&data) == KeyFormat::Minisign {
+        return decode_minisign_public_key(&data);
+    }
+    let pk = parse_ed25519_verifying_key(&data)?;
+    Ok((derive_key_id(&pk), pk))
+}
+
+fn prehash(reader: &mut dyn Read) -> Result<[u8; 64]> {
+    let mut hasher = Blake2b512::new();
+    io::copy(reader, &mut hasher)?;
+    Ok(hasher.finalize().into())
+}
+
+fn global_message(signature: &Signature, trusted_comment: &str) -> Vec<u8> {
+    [&signature.to_bytes()[..], trusted_comment.as_bytes()].concat()
+}
+
+// BLAKE2b-256(Ed || key id || 私钥)
+fn checksum(key_id: &KeyId, keypair: &[u8; 64]) -> [u8; 32] {
+    let mut hasher = Blake2b::<U32>::new();
+    hasher.update(SIG_ALG);
+    hasher.update(key_id);
+    hasher.update(keypair);
+    hasher.finalize().into()
+}
+
+// 加密和解密都是和 scrypt 的输出异或
+fn xor_keynum_sk(key: &mut [u8], passphrase: &[u8]) -> Result<()> {
+    let opslimit = u64::from_le_bytes(key[38..46].try_into()?);
+    let memlimit = u64::from_le_bytes(key[46..54].try_into()?);
+    let params = scrypt_params(opslimit, memlimit)?;
+    let mut stream = Zeroizing::new([0u8; KEYNUM_SK_LEN]);
+    scrypt::scrypt(passphrase, &key[6..38], &params, stream.as_mut())
+        .map_err(|e| anyhow::anyhow!("scrypt failed: {}", e))?;
+    for (byte, mask) in key[SECRET_KEY_LEN - KEYNUM_SK_LEN..]
+        .iter_mut()
+        .zip(stream.iter())
+    {
+        *byte ^= mask;
+    }
+
+    Ok(())
+}
+
+// libsodium crypto_pwhash_scryptsalsa208sha256 根据 opslimit / memlimit 选择参数的方式
+fn scrypt_params(opslimit: u64, memlimit: u64) -> Result<scrypt::Params> {
+    if memlimit > MEMLIMIT_MAX {
+        anyhow::bail!("minisign scrypt memory limit is too large");
+    }
+    let opslimit = opslimit.max(32768);
+    let r = 8u32;
+    let max_n = if opslimit < memlimit / 32 {
+        opslimit / (r as u64 * 4)
+    } else {
+        memlimit / (r as u64 * 128)
+    };
+    let mut n_log2 = 1u8;
+    while n_log2 < 63 && (1u64 << n_log2) <= max_n / 2 {
+        n_log2 += 1;
+    }
+    let p = if opslimit < memlimit / 32 {
+        1
+    } else {
+        let max_rp = ((opslimit / 4) / (1u64 << n_log2)).min(0x3fff_ffff) as u32;
+        max_rp / r
+    };
+    if n_log2 > N_LOG2_MAX || p > P_MAX {
+        anyhow::bail!("minisign scrypt parameters are too large");
+    }
+
+    // len 只用于 PHC 字符串，scrypt() 按输出 buffer 的长度生成
+    scrypt::Params::new(n_log2, r, p, scrypt::Params::RECOMMENDED_LEN)
+        .map_err(|e| anyhow::anyhow!("Invalid scrypt parameters: {}", e))
+}
+
+fn encode_key_box(comment: &str, key: &[u8]) -> String {
+    format!(
+        "{}{}\n{}\n",
+        UNTRUSTED_PREFIX,
+        comment,
+        STANDARD.encode(key)
+    )
+}
+
+fn decode_key_box(data: &[u8], len: usize) -> Result<(&str, Zeroizing<Vec<u8>>)> {
+    let text = std::str::from_utf8(data)?;
+    let mut lines = text.lines().map(|line| line.trim_end_matches('\r'));
+    read_box(&mut lines, len)
+}
+
+// untrusted comment 加一行 base64
+fn read_box<'a>(
+    lines: &mut impl Iterator<Item = &'a str>,
+    len: usize,
+) -> Result<(&'a str, Zeroizing<Vec<u8>>)> {
+    let comment = lines
+        .next()
+        .and_then(|line| line.strip_prefix(UNTRUSTED_PREFIX))
+        .ok_or_else(|| anyhow::anyhow!("Missing \"{}\" line", UNTRUSTED_PREFIX.trim()))?;
+    let encoded = lines
+        .next()
+        .ok_or_else(|| anyhow::anyhow!("Missing base64 line after the untrusted comment"))?;
+    let decoded = Zeroizing::new(STANDARD.decode(encoded.trim())?);
+    if decoded.len() != len {
+        anyhow::bail!("Expected {} bytes, got {}", len, decoded.len());
+    }
+
+    Ok((comment, decoded))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // minisign 文档中的示例公钥和签名，对应的文件内容为 "test"
+    const PUBLIC_KEY: &str = "untrusted comment: minisign public key E7620F1842B4E81F
+RWQf6LRCGA9i53mlYecO4IzT51TGPpvWucNSCh1CBM0QTaLn73Y7GFO3
+";
+    const SIGNATURE: &str = "untrusted comment: signature from minisign secret key
+RUQf6LRCGA9i559r3g7V1qNyJDApGip8MfqcadIgT9CuhV3EMhHoN1mGTkUidF/z7SrlQgXdy8ofjb7bNJJylDOocrCo8KLzZwo=
+trusted comment: timestamp:1633700835\tfile:test\tprehashed
+wLMDjy9FLAuxZ3q4NlEvkgtyhrr0gtTu6KC4KBJdITbbOeAi1zBIYo0v4iTgt8jJpIidRJnp94ABQkJAgAooBQ==
+";
+
+    #[test]
+    fn test_verify_minisign_signature() -> Result<()> {
+        let sig = MinisignSignature::parse(SIGNATURE)?;
+        let (key_id, pk) = decode_minisign_public_key(PUBLIC_KEY.as_bytes())?;
+        assert_eq!(key_id_hex(&key_id), "E7620F1842B4E81F");
+        assert_eq!(sig.key_id, key_id);
+        assert!(sig.prehashed);
+
+        let hash = prehash(&mut &b"test"[..])?;
+        assert!(pk.verify_strict(&hash, &sig.signature).is_ok());
+        let message = global_message(&sig.signature, &sig.trusted_comment);
+        assert!(pk.verify_strict(&message, &sig.global_signature).is_ok());
+        // 输出的格式和输入一致
+        assert_eq!(sig.to_string(), SIGNATURE);
+        Ok(())
+    }
+
+    #[test]
+    fn test_keypair_roundtrip_and_lock() -> Result<()> {
+        let sk = SigningKey::generate(&mut OsRng);
+        let (secret, public) = encode_minisign_keypair(&sk);
+        assert_eq!(detect_key_format(&secret), KeyFormat::Minisign);
+        assert_eq!(detect_key_format(&public), KeyFormat::Minisign);
+        assert!(is_minisign_secret_key(&secret));
+        assert!(!is_minisign_secret_key(&public));
+
+        let (id, loaded) = decode_minisign_secret_key(&secret)?;
+        assert_eq!(loaded.to_bytes(), sk.to_bytes());
+        let (public_id, pk) = decode_minisign_public_key(&public)?;
+        assert_eq!((id, pk), (public_id, sk.verifying_key()));
+
+        let locked = lock_minisign_secret_key(&secret, b"pass")?;
+        assert!(is_encrypted_minisign_key(&locked));
+        assert!(decode_minisign_secret_key(&locked).is_err());
+        let unlocked = unlock_minisign_secret_key(&locked, b"pass")?;
+        assert_eq!(decode_minisign_secret_key(&unlocked)?, (id, loaded));
+        assert!(unlock_minisign_secret_key(&locked, b"wrong").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_scrypt_params_match_minisign() -> Result<()> {
+        let params = scrypt_params(OPSLIMIT, MEMLIMIT)?;
+        assert_eq!((params.log_n(), params.r(), params.p()), (20, 8, 1));
+        assert!(scrypt_params(1 << 40, MEMLIMIT_MAX).is_err());
+        assert!(scrypt_params(OPSLIMIT, 1 << 31).is_err());
+        Ok(())
+    }
+}
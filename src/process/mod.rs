@@ -1,11 +1,39 @@
+mod age;
+mod armor;
 mod b64;
 pub mod csv_convert;
+mod encrypt;
 mod gen_pass;
+mod hash;
+mod hexdump;
+mod html;
 mod http_serve;
+mod jwt;
+mod key_format;
+mod key_info;
+mod key_protect;
+mod manifest;
+mod minisign;
+mod otp;
 mod text;
+mod url_codec;
 
+pub use age::*;
+pub use armor::*;
 pub use b64::*;
 pub use csv_convert::*;
+pub use encrypt::*;
 pub use gen_pass::*;
+pub use hash::*;
+pub use hexdump::*;
+pub use html::*;
 pub use http_serve::*;
+pub use jwt::*;
+pub use key_format::*;
+pub use key_info::*;
+pub use key_protect::*;
+pub use manifest::*;
+pub use minisign::*;
+pub use otp::*;
 pub use text::*;
+pub use url_codec::*;
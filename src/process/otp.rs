@@ -0,0 +1,227 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::Result;
+use data_encoding::BASE32_NOPAD;
+use hmac::digest::KeyInit;
+use hmac::{Hmac, Mac};
+use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+use qrcode::render::unicode;
+use qrcode::QrCode;
+use rand::rngs::OsRng;
+use rand::RngCore;
+use sha1::Sha1;
+use sha2::{Sha256, Sha512};
+
+use crate::OtpAlgorithm;
+
+/// 新生成的 OTP secret 和对应的 otpauth:// URI
+pub struct OtpSecret {
+    // base32，不带 padding，大多数 authenticator 都接受这种格式
+    pub secret: String,
+    pub uri: String,
+}
+
+/// 生成随机 secret 和 otpauth URI，counter 为 Some 时生成 HOTP
+/// URI 格式参考 https://github.com/google/google-authenticator/wiki/Key-Uri-Format
+pub fn process_otp_new(
+    issuer: &str,
+    account: &str,
+    algorithm: OtpAlgorithm,
+    digits: u32,
+    period: u64,
+    counter: Option<u64>,
+    bytes: usize,
+) -> Result<OtpSecret> {
+    verify_digits(digits)?;
+    // 和 process_otp_code 一致，TOTP 的 period 不能为 0
+    if counter.is_none() && period == 0 {
+        anyhow::bail!("Period must be positive");
+    }
+    if bytes < 16 {
+        anyhow::bail!("Secret must be at least 16 bytes");
+    }
+
+    let mut key = vec![0u8; bytes];
+    OsRng.fill_bytes(&mut key);
+    let secret = BASE32_NOPAD.encode(&key);
+
+    let issuer = utf8_percent_encode(issuer, NON_ALPHANUMERIC).to_string();
+    let account = utf8_percent_encode(account, NON_ALPHANUMERIC).to_string();
+    let algorithm = Into::<&str>::into(algorithm).to_uppercase();
+    let mut uri = format!(
+        "otpauth://{}/{}:{}?secret={}&issuer={}&algorithm={}&digits={}",
+        if counter.is_some() { "hotp" } else { "totp" },
+        issuer,
+        account,
+        secret,
+        issuer,
+        algorithm,
+        digits
+    );
+    match counter {
+        Some(counter) => uri.push_str(&format!("&counter={}", counter)),
+        None => uri.push_str(&format!("&period={}", period)),
+    }
+
+    Ok(OtpSecret { secret, uri })
+}
+
+/// 把 URI 渲染成终端中可以扫描的二维码
+pub fn render_qr(data: &str) -> Result<String> {
+    let code = QrCode::new(data.as_bytes())?;
+    let image = code
+        .render::<unicode::Dense1x2>()
+        .dark_color(unicode::Dense1x2::Light)
+        .light_color(unicode::Dense1x2::Dark)
+        .build();
+
+    Ok(image)
+}
+
+/// counter 为 Some 时计算 HOTP，否则按 time（默认当前时间）和 period 计算 TOTP
+pub fn process_otp_code(
+    secret: &str,
+    algorithm: OtpAlgorithm,
+    digits: u32,
+    period: u64,
+    counter: Option<u64>,
+    time: Option<u64>,
+) -> Result<String> {
+    let key = decode_secret(secret)?;
+    let counter = match counter {
+        Some(counter) => counter,
+        None => {
+            if period == 0 {
+                anyhow::bail!("Period must be positive");
+            }
+            let time = match time {
+                Some(time) => time,
+                None => SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs(),
+            };
+            time / period
+        }
+    };
+
+    hotp(&key, counter, digits, algorithm)
+}
+
+/// RFC 4226 HOTP，TOTP 只是把时间窗口作为 counter
+pub fn hotp(key: &[u8], counter: u64, digits: u32, algorithm: OtpAlgorithm) -> Result<String> {
+    verify_digits(digits)?;
+
+    let hash = match algorithm {
+        OtpAlgorithm::Sha1 => hmac::<Hmac<Sha1>>(key, &counter.to_be_bytes())?,
+        OtpAlgorithm::Sha256 => hmac::<Hmac<Sha256>>(key, &counter.to_be_bytes())?,
+        OtpAlgorithm::Sha512 => hmac::<Hmac<Sha512>>(key, &counter.to_be_bytes())?,
+    };
+
+    // dynamic truncation：最后一个字节的低 4 位作为偏移，取 31 bit
+    let offset = (hash[hash.len() - 1] & 0x0f) as usize;
+    let code = u32::from_be_bytes(hash[offset..offset + 4].try_into()?) & 0x7fff_ffff;
+    let code = code % 10u32.pow(digits);
+
+    Ok(format!("{:0width$}", code, width = digits as usize))
+}
+
+fn hmac<M: Mac + KeyInit>(key: &[u8], data: &[u8]) -> Result<Vec<u8>> {
+    let mut mac = <M as KeyInit>::new_from_slice(key)
+        .map_err(|_| anyhow::anyhow!("Invalid HMAC key length"))?;
+    mac.update(data);
+    Ok(mac.finalize().into_bytes().to_vec())
+}
+
+// authenticator 中展示的 secret 经常是小写、带空格分组或带 padding 的
+fn decode_secret(secret: &str) -> Result<Vec<u8>> {
+    let secret: String = secret
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != '=' && *c != '-')
+        .map(|c| c.to_ascii_uppercase())
+        .collect();
+
+    Ok(BASE32_NOPAD.decode(secret.as_bytes())?)
+}
+
+fn verify_digits(digits: u32) -> Result<()> {
+    if !(6..=8).contains(&digits) {
+        anyhow::bail!("Digits must be between 6 and 8");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // RFC 4226 Appendix D
+    #[test]
+    fn test_hotp_rfc4226() -> Result<()> {
+        let key = b"12345678901234567890";
+        let expected = [
+            "755224", "287082", "359152", "969429", "338314", "254676", "287922", "162583",
+            "399871", "520489",
+        ];
+        for (counter, code) in expected.iter().enumerate() {
+            assert_eq!(hotp(key, counter as u64, 6, OtpAlgorithm::Sha1)?, *code);
+        }
+        Ok(())
+    }
+
+    // RFC 6238 Appendix B
+    #[test]
+    fn test_totp_rfc6238() -> Result<()> {
+        let sha1 = BASE32_NOPAD.encode(b"12345678901234567890");
+        let sha256 = BASE32_NOPAD.encode(b"12345678901234567890123456789012");
+        let sha512 = BASE32_NOPAD
+            .encode(b"1234567890123456789012345678901234567890123456789012345678901234");
+
+        let cases = [
+            (59, "94287082", "46119246", "90693936"),
+            (1111111109, "07081804", "68084774", "25091201"),
+            (2000000000, "69279037", "90698825", "38618901"),
+        ];
+        for (time, c1, c256, c512) in cases {
+            let code = |secret: &str, algorithm| {
+                process_otp_code(secret, algorithm, 8, 30, None, Some(time))
+            };
+            assert_eq!(code(&sha1, OtpAlgorithm::Sha1)?, c1);
+            assert_eq!(code(&sha256, OtpAlgorithm::Sha256)?, c256);
+            assert_eq!(code(&sha512, OtpAlgorithm::Sha512)?, c512);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_process_otp_new() -> Result<()> {
+        let otp = process_otp_new(
+            "Acme Co",
+            "alice@acme.com",
+            OtpAlgorithm::Sha1,
+            6,
+            30,
+            None,
+            20,
+        )?;
+        assert_eq!(otp.secret.len(), 32);
+        assert!(otp
+            .uri
+            .starts_with("otpauth://totp/Acme%20Co:alice%40acme%2Ecom?secret="));
+        assert!(otp
+            .uri
+            .ends_with("&issuer=Acme%20Co&algorithm=SHA1&digits=6&period=30"));
+
+        // 生成的 secret 可以直接用于计算 code
+        let lower = otp.secret.to_lowercase();
+        assert_eq!(
+            process_otp_code(&otp.secret, OtpAlgorithm::Sha1, 6, 30, Some(1), None)?,
+            process_otp_code(&lower, OtpAlgorithm::Sha1, 6, 30, Some(1), None)?
+        );
+
+        let otp = process_otp_new("Acme", "alice", OtpAlgorithm::Sha256, 8, 30, Some(0), 20)?;
+        assert!(otp.uri.starts_with("otpauth://hotp/"));
+        assert!(otp.uri.ends_with("&algorithm=SHA256&digits=8&counter=0"));
+        assert!(render_qr(&otp.uri).is_ok());
+
+        assert!(process_otp_new("Acme", "alice", OtpAlgorithm::Sha1, 6, 0, None, 20).is_err());
+        Ok(())
+    }
+}
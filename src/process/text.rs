@@ -1,20 +1,30 @@
 use std::collections::HashMap;
 use std::fs;
-use std::io::Read;
+use std::io::{self, Read};
 use std::path::Path;
 
 use anyhow::Result;
 use base64::engine::general_purpose::URL_SAFE_NO_PAD;
 use base64::Engine;
-use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use ed25519_dalek::{Signature, SigningKey, VerifyingKey};
 use rand::rngs::OsRng;
-
-use crate::{get_reader, process_genpass, TextSignFormat};
+use rand::RngCore;
+use sha2::{Digest, Sha512};
+use zeroize::Zeroizing;
+
+use super::key_format::{
+    decode_blake3_key, encode_blake3_key, encode_ed25519_keypair, parse_ed25519_signing_key,
+    parse_ed25519_verifying_key,
+};
+use super::key_protect::{lock_key, read_key_file};
+use crate::{
+    get_reader, process_age_keygen, write_file, write_secret_file, KeyFormat, TextKeyType,
+    TextSignFormat,
+};
 
 pub fn process_text_sign(input: &str, key: &str, format: TextSignFormat) -> Result<String> {
+    // reader 直接交给 signer 分块读取，不能提前读完，否则签名的是空数据
     let mut reader = get_reader(input)?;
-    let mut buf = Vec::new();
-    reader.read_to_end(&mut buf)?;
 
     let signed = match format {
         TextSignFormat::Blake3 => {
@@ -33,42 +43,65 @@ pub fn process_text_sign(input: &str, key: &str, format: TextSignFormat) -> Resu
     Ok(signed)
 }
 
+/// 签名不匹配时返回 TextError::SignatureMismatch，和签名格式错误、key 错误区分开
 pub fn process_text_verify(
     input: &str,
     key: &str,
     format: TextSignFormat,
     sig: &str,
-) -> Result<bool> {
-    let mut reader = get_reader(input)?;
+) -> Result<()> {
+    let reader = get_reader(input)?;
 
-    let sig = URL_SAFE_NO_PAD.decode(sig)?;
-
-    let mut buf = Vec::new();
-    reader.read_to_end(&mut buf)?;
+    let sig = URL_SAFE_NO_PAD
+        .decode(sig.trim())
+        .map_err(|e| TextError::MalformedSignature(e.to_string()))?;
 
     let verified = match format {
         TextSignFormat::Blake3 => {
             let verifier = Blake3::load(key)?;
 
-            verifier.verify(&mut reader, &sig)?
+            verifier.verify(reader, &sig)?
         }
         TextSignFormat::Ed25519 => {
             let verifier = Ed25519Verifier::load(key)?;
-            verifier.verify(&mut reader, &sig)?
+            verifier.verify(reader, &sig)?
         }
     };
 
-    Ok(verified)
+    if !verified {
+        return Err(TextError::SignatureMismatch.into());
+    }
+
+    Ok(())
 }
 
-trait TextSign {
+#[derive(Debug, thiserror::Error)]
+pub enum TextError {
+    #[error("invalid {kind} key: expected {expected} bytes, got {actual}")]
+    KeyLength {
+        kind: &'static str,
+        expected: usize,
+        actual: usize,
+    },
+
+    #[error("invalid {kind} key: {reason}")]
+    InvalidKey { kind: &'static str, reason: String },
+
+    #[error("malformed signature: {0}")]
+    MalformedSignature(String),
+
+    #[error("signature mismatch")]
+    SignatureMismatch,
+}
+
+pub(crate) trait TextSign {
     // &[u8] impl Read, so we can
     // sign the data from the reader and return the signature
     // 代码体积小，但是性能一般，但 dispatch 比 io 效率高很多
     fn sign(&self, reader: &mut dyn Read) -> Result<Vec<u8>>;
 }
 
-trait TextVerify {
+pub(crate) trait TextVerify {
     // 产生的代码体大，性能好
     // 在 trait 的接口中，对于 owned 的 value，不需要再额外的加 mut
     // 但是在使用时，需要显式的加 mut
@@ -76,52 +109,50 @@ trait TextVerify {
     fn verify<R: Read>(&self, reader: R, sig: &[u8]) -> Result<bool>;
 }
 
-trait KeyLoader {
+pub(crate) trait KeyLoader {
     fn load(path: impl AsRef<Path>) -> Result<Self>
     where
         // 返回定长的数据结构
         Self: Sized; // marker trait ，需要有这种行为，说明 Self 是有固定长度的数据结构，str [u8] 这些不是有固定长度的
 }
 
-struct Blake3 {
-    key: [u8; 32],
+pub(crate) struct Blake3 {
+    pub(crate) key: [u8; 32],
 }
 
-struct Ed25519Singer {
-    key: SigningKey,
+pub(crate) struct Ed25519Singer {
+    pub(crate) key: SigningKey,
 }
 
-struct Ed25519Verifier {
-    key: VerifyingKey,
+pub(crate) struct Ed25519Verifier {
+    pub(crate) key: VerifyingKey,
 }
 
 impl TextSign for Blake3 {
     fn sign(&self, reader: &mut dyn Read) -> Result<Vec<u8>> {
-        // todo : improve perf by reading in chunks
-        let mut buf = Vec::new();
-        reader.read_to_end(&mut buf)?;
-
-        Ok(blake3::keyed_hash(&self.key, &buf).as_bytes().to_vec())
+        Ok(self.keyed_hash(reader)?.as_bytes().to_vec())
     }
 }
 
 impl TextVerify for Blake3 {
     fn verify<R: Read>(&self, mut reader: R, sig: &[u8]) -> Result<bool> {
-        let mut buf = Vec::new();
-        reader.read_to_end(&mut buf)?;
-        // let hash = blake3::hash(&buf).as_bytes();
-        let hash = blake3::keyed_hash(&self.key, &buf);
-        // 重新绑定后，生命周期到函数结束
-        let hash = hash.as_bytes();
-        Ok(hash == sig)
+        let sig: [u8; blake3::OUT_LEN] = sig.try_into().map_err(|_| {
+            TextError::MalformedSignature(format!(
+                "expected {} bytes, got {}",
+                blake3::OUT_LEN,
+                sig.len()
+            ))
+        })?;
+        let hash = self.keyed_hash(&mut reader)?;
+        // blake3::Hash 的 == 是常量时间比较，不会因为提前返回泄露匹配的字节数
+        Ok(hash == blake3::Hash::from(sig))
     }
 }
 
+// 使用 Ed25519ph（RFC 8032），先对数据做 SHA-512，可以分块处理任意大小的输入
 impl TextSign for Ed25519Singer {
     fn sign(&self, reader: &mut dyn Read) -> Result<Vec<u8>> {
-        let mut buf = Vec::new();
-        reader.read_to_end(&mut buf)?;
-        let sig = self.key.sign(&buf);
+        let sig = self.key.sign_prehashed(prehash(reader)?, None)?;
 
         Ok(sig.to_bytes().to_vec())
     }
@@ -129,21 +160,30 @@ impl TextSign for Ed25519Singer {
 
 impl TextVerify for Ed25519Verifier {
     fn verify<R: Read>(&self, mut reader: R, sig: &[u8]) -> Result<bool> {
-        let mut buf = Vec::new();
-        reader.read_to_end(&mut buf)?;
-
-        let sig = Signature::from_bytes(sig.try_into()?);
-        let ret = self.key.verify(&buf, &sig).is_ok();
+        let sig = Signature::from_slice(sig).map_err(|_| {
+            TextError::MalformedSignature(format!("expected 64 bytes, got {}", sig.len()))
+        })?;
+        let ret = self
+            .key
+            .verify_prehashed(prehash(&mut reader)?, None, &sig)
+            .is_ok();
         Ok(ret)
     }
 }
 
+fn prehash(reader: &mut dyn Read) -> Result<Sha512> {
+    let mut hasher = Sha512::new();
+    io::copy(reader, &mut hasher)?;
+    Ok(hasher)
+}
+
 impl KeyLoader for Blake3 {
     fn load(path: impl AsRef<Path>) -> Result<Self>
     where
         Self: Sized,
     {
-        let key = fs::read(path)?;
+        // 加密的私钥文件会提示输入口令
+        let key = read_key_file(path)?;
         Self::try_new(&key)
     }
 }
@@ -153,22 +193,31 @@ impl Blake3 {
         Self { key }
     }
 
+    // blake3::Hasher 实现了 Write，io::copy 每次只读取一块数据
+    fn keyed_hash(&self, reader: &mut dyn Read) -> Result<blake3::Hash> {
+        let mut hasher = blake3::Hasher::new_keyed(&self.key);
+        io::copy(reader, &mut hasher)?;
+        Ok(hasher.finalize())
+    }
+
+    // hex / base64 编码，或者旧版本的 ASCII key
     fn try_new(key: &[u8]) -> Result<Self> {
-        let key = &key[..32];
-        let key = key.try_into()?;
+        let (key, _) = decode_blake3_key(key)?;
         let signer = Blake3::new(key);
         Ok(signer)
     }
 
     fn load(path: impl AsRef<Path>) -> Result<Self> {
-        let key = fs::read(path)?;
+        let key = read_key_file(path)?;
         Self::try_new(&key)
     }
 
+    // 以前用 genpass 生成可打印字符，熵远低于 256 位，现在直接使用 OsRng 的 32 字节
     fn generate() -> Result<HashMap<&'static str, Vec<u8>>> {
-        let key = process_genpass(32, true, true, true, true)?;
+        let mut key = Zeroizing::new([0u8; blake3::KEY_LEN]);
+        OsRng.fill_bytes(key.as_mut());
         let mut map = HashMap::new();
-        map.insert("blake3.txt", key.as_bytes().to_vec());
+        map.insert("blake3.txt", encode_blake3_key(&key).to_vec());
 
         Ok(map)
     }
@@ -179,7 +228,8 @@ impl KeyLoader for Ed25519Singer {
     where
         Self: Sized,
     {
-        let key = fs::read(path)?;
+        // 加密的私钥文件会提示输入口令
+        let key = read_key_file(path)?;
         Self::try_new(&key)
     }
 }
@@ -189,25 +239,25 @@ impl Ed25519Singer {
         Self { key }
     }
 
+    // 自动识别 raw / PEM / OpenSSH / JWK
     fn try_new(key: &[u8]) -> Result<Self> {
-        // 生成 SigningKey 的方式 key 和 signer 对应
-        let key = SigningKey::from_bytes(key.try_into()?);
+        let key = parse_ed25519_signing_key(key)?;
         let singer = Ed25519Singer::new(key);
         Ok(singer)
     }
 
     fn load(path: impl AsRef<Path>) -> Result<Self> {
-        let key = fs::read(path)?;
+        let key = read_key_file(path)?;
         Self::try_new(&key)
     }
 
-    fn generate() -> Result<HashMap<&'static str, Vec<u8>>> {
+    fn generate(key_format: KeyFormat) -> Result<HashMap<&'static str, Vec<u8>>> {
         let mut csprng = OsRng;
         let sk = SigningKey::generate(&mut csprng);
-        let pk: VerifyingKey = (&sk).into();
+        let (private, public) = encode_ed25519_keypair(&sk, key_format)?;
         let mut map = HashMap::new();
-        map.insert("ed25519.sk", sk.to_bytes().to_vec());
-        map.insert("ed25519.pk", pk.to_bytes().to_vec());
+        map.insert("ed25519.sk", private.to_vec());
+        map.insert("ed25519.pk", public);
 
         Ok(map)
     }
@@ -218,7 +268,8 @@ impl KeyLoader for Ed25519Verifier {
     where
         Self: Sized,
     {
-        let key = fs::read(path)?;
+        // 加密的私钥文件会提示输入口令
+        let key = read_key_file(path)?;
         Self::try_new(&key)
     }
 }
@@ -228,29 +279,103 @@ impl Ed25519Verifier {
         Self { key }
     }
 
+    // 公钥格式同样自动识别，传入私钥文件时从中推导公钥
     fn try_new(key: &[u8]) -> Result<Self> {
-        let key = VerifyingKey::from_bytes(key.try_into()?)?;
+        let key = parse_ed25519_verifying_key(key)?;
         let singer = Ed25519Verifier::new(key);
         Ok(singer)
     }
 
     fn load(path: impl AsRef<Path>) -> Result<Self> {
-        let key = fs::read(path)?;
+        let key = read_key_file(path)?;
         Self::try_new(&key)
     }
 }
 
+// x25519.sk 和 age-keygen 生成的 identity 文件格式一致，x25519.pk 为 age1... 公钥
+fn generate_x25519() -> Result<HashMap<&'static str, Vec<u8>>> {
+    let (identity, recipient) = process_age_keygen()?;
+    let mut map = HashMap::new();
+    map.insert("x25519.sk", identity.as_bytes().to_vec());
+    map.insert("x25519.pk", format!("{}\n", recipient).into_bytes());
+
+    Ok(map)
+}
+
 // blake 生成的是一个 key
 // ed25519 是生成一对 key
-pub fn process_text_key_generate(format: TextSignFormat) -> Result<HashMap<&'static str, Vec<u8>>> {
-    match format {
-        TextSignFormat::Blake3 => Blake3::generate(),
-        TextSignFormat::Ed25519 => Ed25519Singer::generate(),
+// --key-format 只对 ed25519 有效
+// 指定了 passphrase 时加密私钥，公钥（.pk）不加密
+pub fn process_text_key_generate(
+    key_type: TextKeyType,
+    key_format: KeyFormat,
+    passphrase: Option<&[u8]>,
+) -> Result<HashMap<&'static str, Vec<u8>>> {
+    if !matches!(key_type, TextKeyType::Ed25519) && key_format != KeyFormat::Raw {
+        anyhow::bail!(
+            "--key-format {} is only supported for ed25519 keys",
+            key_format
+        );
+    }
+    let mut map = match key_type {
+        TextKeyType::Blake3 => Blake3::generate(),
+        TextKeyType::Ed25519 => Ed25519Singer::generate(key_format),
+        TextKeyType::X25519 => generate_x25519(),
+    }?;
+    if let Some(passphrase) = passphrase {
+        for (name, content) in map.iter_mut() {
+            if !is_public_key_file(name) {
+                *content = lock_key(content, passphrase)?.to_vec();
+            }
+        }
+    }
+
+    Ok(map)
+}
+
+/// 写入生成的密钥文件，私钥的权限为 0600
+/// 任意一个文件已经存在时都不写入，除非指定了 force，已经写入的文件会被删除
+pub fn write_key_files(
+    dir: &Path,
+    keys: &HashMap<&'static str, Vec<u8>>,
+    force: bool,
+) -> Result<()> {
+    let mut written = Vec::new();
+    for (name, content) in keys {
+        let path = dir.join(name);
+        let ret = if is_public_key_file(name) {
+            write_file(&path, content, force)
+        } else {
+            write_secret_file(&path, content, force)
+        };
+        if let Err(e) = ret {
+            for path in &written {
+                let _ = fs::remove_file(path);
+            }
+            let exists = e
+                .downcast_ref::<io::Error>()
+                .is_some_and(|e| e.kind() == io::ErrorKind::AlreadyExists);
+            if exists {
+                anyhow::bail!(
+                    "{} already exists, use --force to overwrite it",
+                    path.display()
+                );
+            }
+            return Err(e);
+        }
+        written.push(path);
     }
+
+    Ok(())
+}
+
+fn is_public_key_file(name: &str) -> bool {
+    name.ends_with(".pk")
 }
 
 #[cfg(test)]
 mod tests {
+    use ed25519_dalek::{Signer, Verifier};
     use rand::rngs::OsRng;
 
     use super::*;
@@ -292,7 +417,7 @@ mod tests {
         let signing_key = SigningKey::generate(&mut csprng);
         // 由 private 推导出 public
         // 只能通过 返回值进行 into 的类型推导
-        let verify_key: VerifyingKey = (&signing_key).into();
+        let _verify_key: VerifyingKey = (&signing_key).into();
 
         let verify_key = signing_key.verifying_key();
 
@@ -303,6 +428,81 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_process_text_sign_hello() -> Result<()> {
+        // fixtures 中的签名是对 fixtures/hello.txt 内容的签名
+        for (format, key, pk, sig) in [
+            (
+                TextSignFormat::Blake3,
+                "fixtures/blake3.txt",
+                "fixtures/blake3.txt",
+                "fixtures/hello-blake3-sig.txt",
+            ),
+            (
+                TextSignFormat::Ed25519,
+                "fixtures/ed25519.sk",
+                "fixtures/ed25519.pk",
+                "fixtures/hello-ed25519-sig.txt",
+            ),
+        ] {
+            let signed = process_text_sign("fixtures/hello.txt", key, format)?;
+            assert_eq!(signed, fs::read_to_string(sig)?.trim());
+            process_text_verify("fixtures/hello.txt", pk, format, &signed)?;
+            // 不同的消息签名不同，也不能通过验证
+            let other = process_text_sign("Cargo.toml", key, format)?;
+            assert_ne!(signed, other);
+            let err = process_text_verify("Cargo.toml", pk, format, &signed).unwrap_err();
+            assert!(matches!(
+                err.downcast_ref::<TextError>(),
+                Some(TextError::SignatureMismatch)
+            ));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_sign_is_not_empty_message() -> Result<()> {
+        let blake3 = Blake3::load("fixtures/blake3.txt")?;
+        let empty = blake3.sign(&mut &b""[..])?;
+        let hello = blake3.sign(&mut &fs::read("fixtures/hello.txt")?[..])?;
+        assert_ne!(empty, hello);
+        assert_eq!(
+            hello,
+            blake3::keyed_hash(&blake3.key, b"hello world").as_bytes()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_malformed_signature() {
+        for (format, key, sig) in [
+            (TextSignFormat::Blake3, "fixtures/blake3.txt", "AAAA"),
+            (TextSignFormat::Ed25519, "fixtures/ed25519.pk", "AAAA"),
+            (TextSignFormat::Blake3, "fixtures/blake3.txt", "not base64!"),
+        ] {
+            let err = process_text_verify("fixtures/hello.txt", key, format, sig).unwrap_err();
+            assert!(matches!(
+                err.downcast_ref::<TextError>(),
+                Some(TextError::MalformedSignature(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_short_keys_are_rejected() {
+        // 以前 &key[..32] 会直接 panic
+        for key in [&b"short"[..], &[0u8; 31][..], &[0u8; 64][..]] {
+            let err = Blake3::try_new(key).err().unwrap();
+            assert!(matches!(
+                err.downcast_ref::<TextError>(),
+                Some(TextError::KeyLength { expected: 32, .. })
+            ));
+            assert!(Ed25519Singer::try_new(key).is_err());
+            assert!(Ed25519Verifier::try_new(key).is_err());
+        }
+        assert!(Blake3::try_new(b"2Ne5^nLCyUR1#CFMD*9otqvC5HwKmdVX\n").is_ok());
+    }
+
     #[test]
     fn test_ed25519() -> Result<()> {
         let mut csprng = OsRng;
@@ -314,10 +514,31 @@ mod tests {
         let result = URL_SAFE_NO_PAD.encode(&mut signature);
         println!("{:?}", result);
 
-        let verifier = Ed25519Verifier::try_new(&key)?;
+        // 公钥要从私钥推导，随机的 32 字节不一定是合法的公钥
+        let verifier = Ed25519Verifier::try_new(signing_key.verifying_key().as_bytes())?;
 
         let result = verifier.verify(&mut &message[..], &signature).is_ok();
         println!("verify result = {}", result);
         Ok(())
     }
+
+    #[test]
+    fn test_write_key_files_does_not_overwrite() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let keys = process_text_key_generate(TextKeyType::Ed25519, KeyFormat::Raw, None)?;
+        fs::write(dir.path().join("ed25519.pk"), "existing")?;
+
+        let err = write_key_files(dir.path(), &keys, false).unwrap_err();
+        assert!(err.to_string().contains("already exists"));
+        // 已经存在的文件不变，也不会留下只写了一半的密钥对
+        assert_eq!(
+            fs::read_to_string(dir.path().join("ed25519.pk"))?,
+            "existing"
+        );
+        assert!(!dir.path().join("ed25519.sk").exists());
+
+        write_key_files(dir.path(), &keys, true)?;
+        assert_eq!(fs::read(dir.path().join("ed25519.pk"))?, keys["ed25519.pk"]);
+        Ok(())
+    }
 }
@@ -0,0 +1,167 @@
+use std::io::Write;
+
+use anyhow::Result;
+use percent_encoding::{percent_decode, utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use serde::Serialize;
+use url::{form_urlencoded, Url};
+
+use crate::{get_writer, read_text, UrlMode};
+
+// RFC 3986 的 unreserved 字符：字母、数字和 - . _ ~
+const COMPONENT: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~');
+
+// 和 JavaScript 的 encodeURI 一致，额外保留 reserved 字符
+const FULL: &AsciiSet = &COMPONENT
+    .remove(b';')
+    .remove(b',')
+    .remove(b'/')
+    .remove(b'?')
+    .remove(b':')
+    .remove(b'@')
+    .remove(b'&')
+    .remove(b'=')
+    .remove(b'+')
+    .remove(b'$')
+    .remove(b'#')
+    .remove(b'!')
+    .remove(b'\'')
+    .remove(b'(')
+    .remove(b')')
+    .remove(b'*');
+
+#[derive(Debug, Serialize)]
+pub struct ParsedUrl {
+    pub scheme: String,
+    pub username: String,
+    pub password: Option<String>,
+    pub host: Option<String>,
+    // 没有显式指定时为 scheme 的默认端口
+    pub port: Option<u16>,
+    pub path: String,
+    pub query: Option<String>,
+    pub fragment: Option<String>,
+    // 保留顺序和重复的参数，值已经解码
+    pub query_params: Vec<(String, String)>,
+}
+
+pub fn process_url_encode(input: &str, output: &str, mode: UrlMode) -> Result<()> {
+    let data = read_text(input)?;
+    let mut writer = get_writer(output)?;
+    writeln!(writer, "{}", url_encode(&data, mode))?;
+    writer.flush()?;
+
+    Ok(())
+}
+
+/// 解码结果可能不是 UTF-8，直接输出原始字节
+pub fn process_url_decode(input: &str, output: &str, mode: UrlMode) -> Result<()> {
+    let data = read_text(input)?;
+    let mut writer = get_writer(output)?;
+    writer.write_all(&url_decode(&data, mode))?;
+    writer.flush()?;
+
+    Ok(())
+}
+
+pub fn process_url_parse(input: &str) -> Result<String> {
+    let data = read_text(input)?;
+    let parsed = parse_url(data.trim())?;
+    Ok(serde_json::to_string_pretty(&parsed)?)
+}
+
+pub fn url_encode(data: &str, mode: UrlMode) -> String {
+    match mode {
+        UrlMode::Component => utf8_percent_encode(data, COMPONENT).to_string(),
+        UrlMode::Full => utf8_percent_encode(data, FULL).to_string(),
+        UrlMode::Form => form_urlencoded::byte_serialize(data.as_bytes()).collect(),
+    }
+}
+
+pub fn url_decode(data: &str, mode: UrlMode) -> Vec<u8> {
+    match mode {
+        UrlMode::Component | UrlMode::Full => percent_decode(data.as_bytes()).collect(),
+        UrlMode::Form => {
+            let data = data.replace('+', " ");
+            percent_decode(data.as_bytes()).collect()
+        }
+    }
+}
+
+pub fn parse_url(data: &str) -> Result<ParsedUrl> {
+    let url = Url::parse(data)?;
+
+    Ok(ParsedUrl {
+        scheme: url.scheme().to_string(),
+        username: url.username().to_string(),
+        password: url.password().map(String::from),
+        host: url.host_str().map(String::from),
+        port: url.port_or_known_default(),
+        path: url.path().to_string(),
+        query: url.query().map(String::from),
+        fragment: url.fragment().map(String::from),
+        query_params: url.query_pairs().into_owned().collect(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_url_encode_modes() {
+        let data = "a b&c=d/é?x~";
+        assert_eq!(
+            url_encode(data, UrlMode::Component),
+            "a%20b%26c%3Dd%2F%C3%A9%3Fx~"
+        );
+        assert_eq!(url_encode(data, UrlMode::Full), "a%20b&c=d/%C3%A9?x~");
+        assert_eq!(
+            url_encode(data, UrlMode::Form),
+            "a+b%26c%3Dd%2F%C3%A9%3Fx%7E"
+        );
+    }
+
+    #[test]
+    fn test_url_decode_modes() {
+        assert_eq!(
+            url_decode("a+b%20c%C3%A9", UrlMode::Component),
+            "a+b cé".as_bytes()
+        );
+        assert_eq!(
+            url_decode("a+b%20c%C3%A9", UrlMode::Form),
+            "a b cé".as_bytes()
+        );
+        // 非 UTF-8 的字节原样输出
+        assert_eq!(url_decode("%FF%00", UrlMode::Full), [0xff, 0x00]);
+        for mode in [UrlMode::Component, UrlMode::Full, UrlMode::Form] {
+            let data = "key=a b&c/中文";
+            assert_eq!(url_decode(&url_encode(data, mode), mode), data.as_bytes());
+        }
+    }
+
+    #[test]
+    fn test_parse_url() -> Result<()> {
+        let url = parse_url("https://user:pw@example.com/a%20b/c?x=1&y=hello+world&x=2#top")?;
+        assert_eq!(url.scheme, "https");
+        assert_eq!(url.username, "user");
+        assert_eq!(url.password.as_deref(), Some("pw"));
+        assert_eq!(url.host.as_deref(), Some("example.com"));
+        assert_eq!(url.port, Some(443));
+        assert_eq!(url.path, "/a%20b/c");
+        assert_eq!(url.fragment.as_deref(), Some("top"));
+        assert_eq!(
+            url.query_params,
+            vec![
+                ("x".to_string(), "1".to_string()),
+                ("y".to_string(), "hello world".to_string()),
+                ("x".to_string(), "2".to_string()),
+            ]
+        );
+        assert!(parse_url("not a url").is_err());
+        Ok(())
+    }
+}
@@ -1,5 +1,8 @@
-use std::fs::File;
-use std::io::Read;
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Read, Write};
+use std::path::Path;
+
+use zeroize::Zeroizing;
 
 pub fn get_reader(input: &str) -> anyhow::Result<Box<dyn Read>> {
     // 不同的数据类型，将他们提升到 dyn trait
@@ -14,3 +17,107 @@ pub fn get_reader(input: &str) -> anyhow::Result<Box<dyn Read>> {
 
     Ok(reader)
 }
+
+pub fn get_writer(output: &str) -> anyhow::Result<Box<dyn Write>> {
+    // 和 get_reader 对应，- 表示 stdout，加一层 BufWriter 减少系统调用
+    let writer: Box<dyn Write> = if output == "-" {
+        Box::new(BufWriter::new(std::io::stdout()))
+    } else {
+        Box::new(BufWriter::new(File::create(output)?))
+    };
+
+    Ok(writer)
+}
+
+/// 读取全部输入作为文本，去掉 echo 带来的最后一个换行，中间的换行保留
+pub fn read_text(input: &str) -> anyhow::Result<String> {
+    let mut buf = String::new();
+    get_reader(input)?.read_to_string(&mut buf)?;
+    if buf.ends_with('\n') {
+        buf.pop();
+        if buf.ends_with('\r') {
+            buf.pop();
+        }
+    }
+
+    Ok(buf)
+}
+
+/// 写入密码、密钥等敏感内容，unix 下文件权限为 0600，只有当前用户可读写
+/// overwrite 为 false 时文件已存在则报错
+pub fn write_secret_file(
+    path: impl AsRef<Path>,
+    content: &[u8],
+    overwrite: bool,
+) -> anyhow::Result<()> {
+    let mut options = file_options(overwrite);
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+        options.mode(0o600);
+        let mut file = options.open(path.as_ref())?;
+        // mode 只在创建文件时生效，已存在的文件需要重新设置权限
+        file.set_permissions(std::fs::Permissions::from_mode(0o600))?;
+        file.write_all(content)?;
+    }
+
+    #[cfg(not(unix))]
+    {
+        let mut file = options.open(path.as_ref())?;
+        file.write_all(content)?;
+    }
+
+    Ok(())
+}
+
+/// 写入公钥等普通文件，overwrite 为 false 时文件已存在则报错
+pub fn write_file(path: impl AsRef<Path>, content: &[u8], overwrite: bool) -> anyhow::Result<()> {
+    file_options(overwrite)
+        .open(path.as_ref())?
+        .write_all(content)?;
+    Ok(())
+}
+
+// 不覆盖时用 create_new，由 open 判断文件是否存在，不会在检查和写入之间被抢先创建
+fn file_options(overwrite: bool) -> OpenOptions {
+    let mut options = OpenOptions::new();
+    options.write(true);
+    if overwrite {
+        options.create(true).truncate(true);
+    } else {
+        options.create_new(true);
+    }
+    options
+}
+
+/// 读取 master secret / passphrase 等敏感输入
+/// 指定了文件则从文件读取（去掉结尾换行），否则从 TTY 读取且不回显
+pub fn read_secret(path: Option<&str>, prompt: &str) -> anyhow::Result<Zeroizing<Vec<u8>>> {
+    let mut secret = Zeroizing::new(match path {
+        Some(path) => std::fs::read(path)?,
+        None => rpassword::prompt_password(prompt)?.into_bytes(),
+    });
+
+    while matches!(secret.last(), Some(b'\n' | b'\r')) {
+        secret.pop();
+    }
+    if secret.is_empty() {
+        anyhow::bail!("Secret must not be empty");
+    }
+
+    Ok(secret)
+}
+
+/// 读取新设置的口令，从 TTY 输入时需要输入两次，避免输错后无法解密
+pub fn read_new_secret(path: Option<&str>, prompt: &str) -> anyhow::Result<Zeroizing<Vec<u8>>> {
+    let secret = read_secret(path, prompt)?;
+    if path.is_none() {
+        let again = read_secret(None, "Confirm passphrase: ")?;
+        if secret != again {
+            anyhow::bail!("Passphrases do not match");
+        }
+    }
+
+    Ok(secret)
+}
@@ -1,4 +1,3 @@
-use base64::Engine;
 use crypto::ed25519;
 use rand::rngs::OsRng;
 use rand::seq::SliceRandom;
@@ -77,11 +76,11 @@ fn test_ed25519() {
     // 然后使用这个密钥生成一个 Ed25519 的公钥/私钥对
     // 然后将这个公钥/私钥对存储在 wallet 结构中
     let mut key = [0u8; 32];
-    let mut rand = OsRng::default();
+    let mut rand = OsRng;
 
     // todo  Fill dest with random data.
     rand.fill_bytes(&mut key);
     let (secret_key, public_key) = ed25519::keypair(&key);
-    let secret_key = secret_key.to_vec();
-    let public_key = public_key.to_vec();
+    let _secret_key = secret_key.to_vec();
+    let _public_key = public_key.to_vec();
 }